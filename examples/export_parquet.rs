@@ -0,0 +1,112 @@
+extern crate gctu;
+
+use gctu::common::TraceRecord;
+use gctu::job_events::JobEventIterator;
+use gctu::machine_attributes::MachineAttributeIterator;
+use gctu::machine_events::MachineEventIterator;
+use gctu::parquet::{self, ParquetWriter};
+use gctu::task_constraints::TaskConstraintIterator;
+use gctu::task_events::TaskEventIterator;
+use gctu::task_usage::TaskUsageIterator;
+use serde::Serialize;
+
+static TABLES: [&str; 6] = [
+    "machine_events",
+    "machine_attributes",
+    "job_events",
+    "task_events",
+    "task_constraints",
+    "task_usage",
+];
+
+fn export<T, I>(records: I, out_dir: &str, row_group_duration: u64) -> Result<(), gctu::Error>
+where
+    T: TraceRecord + Serialize,
+    I: Iterator<Item = Result<T, gctu::Error>>,
+{
+    let mut writer = ParquetWriter::<T>::with_row_group_duration(out_dir, row_group_duration)?;
+    for r in records {
+        writer.write(&r?)?;
+    }
+    let rows = writer.finish()?;
+    println!(
+        "{}: {} rows -> {}",
+        T::TABLE,
+        rows,
+        parquet::parquet_path::<T>(out_dir).display()
+    );
+    Ok(())
+}
+
+fn main() -> Result<(), gctu::Error> {
+    use clap::{App, Arg};
+
+    let args = App::new("export_parquet")
+        .version("0.1")
+        .about("Converts the trace's tables to Parquet files")
+        .arg(
+            Arg::with_name("trace-path")
+                .short("t")
+                .long("trace-path")
+                .value_name("PATH")
+                .default_value("/data/google-trace/clusterdata-2011-2")
+                .help("Path to the Google cluster trace"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("DIR")
+                .required(true)
+                .help("Directory to write one <table>.parquet file per table to"),
+        )
+        .arg(
+            Arg::with_name("table")
+                .long("table")
+                .value_name("TABLE")
+                .multiple(true)
+                .possible_values(&TABLES)
+                .help("Table to export (default: all tables)"),
+        )
+        .arg(
+            Arg::with_name("row-group-seconds")
+                .long("row-group-seconds")
+                .value_name("N")
+                .default_value("3600")
+                .help("Span of trace time covered by each row group"),
+        )
+        .get_matches();
+
+    let trace_path = args.value_of("trace-path").unwrap();
+    let out_dir = args.value_of("output").unwrap();
+    let tables: Vec<&str> = match args.values_of("table") {
+        Some(t) => t.collect(),
+        None => TABLES.to_vec(),
+    };
+    let seconds: u64 = args
+        .value_of("row-group-seconds")
+        .unwrap()
+        .parse()
+        .expect("row group span must be a whole number of seconds");
+    let duration = seconds * 1_000_000;
+
+    for table in tables {
+        match table {
+            "machine_events" => export(MachineEventIterator::new(trace_path)?, out_dir, duration)?,
+            "machine_attributes" => export(
+                MachineAttributeIterator::new(trace_path)?,
+                out_dir,
+                duration,
+            )?,
+            "job_events" => export(JobEventIterator::new(trace_path)?, out_dir, duration)?,
+            "task_events" => export(TaskEventIterator::new(trace_path)?, out_dir, duration)?,
+            "task_constraints" => {
+                export(TaskConstraintIterator::new(trace_path)?, out_dir, duration)?
+            }
+            "task_usage" => export(TaskUsageIterator::new(trace_path)?, out_dir, duration)?,
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+extern crate gctu;
+
+use gctu::common::TraceRecord;
+use gctu::job_events::JobEvent;
+use gctu::machine_attributes::MachineAttribute;
+use gctu::machine_events::MachineEvent;
+use gctu::sqlite::Database;
+use gctu::task_constraints::TaskConstraint;
+use gctu::task_events::TaskEvent;
+use gctu::task_usage::TaskUsageRecord;
+use serde::Serialize;
+
+static TABLES: [&str; 6] = [
+    "machine_events",
+    "machine_attributes",
+    "job_events",
+    "task_events",
+    "task_constraints",
+    "task_usage",
+];
+
+fn load<T: TraceRecord + Serialize>(
+    db: &mut Database,
+    trace_path: &str,
+    start: u64,
+    end: u64,
+) -> Result<(), gctu::Error> {
+    let rows = db.load_range::<T>(trace_path, start, end)?;
+    println!("{}: {} rows", T::TABLE, rows);
+    Ok(())
+}
+
+fn seconds(args: &clap::ArgMatches, name: &str) -> Option<u64> {
+    args.value_of(name).map(|s| {
+        let s: u64 = s.parse().expect("times must be whole numbers of seconds");
+        s * 1_000_000
+    })
+}
+
+fn main() -> Result<(), gctu::Error> {
+    use clap::{App, Arg};
+
+    let args = App::new("load_sqlite")
+        .version("0.1")
+        .about("Loads the trace's tables into a SQLite database")
+        .arg(
+            Arg::with_name("trace-path")
+                .short("t")
+                .long("trace-path")
+                .value_name("PATH")
+                .default_value("/data/google-trace/clusterdata-2011-2")
+                .help("Path to the Google cluster trace"),
+        )
+        .arg(
+            Arg::with_name("database")
+                .short("d")
+                .long("database")
+                .value_name("FILE")
+                .required(true)
+                .help("SQLite database to load the tables into (created if missing)"),
+        )
+        .arg(
+            Arg::with_name("table")
+                .long("table")
+                .value_name("TABLE")
+                .multiple(true)
+                .possible_values(&TABLES)
+                .help("Table to load (default: all tables)"),
+        )
+        .arg(
+            Arg::with_name("start")
+                .long("start")
+                .value_name("SECONDS")
+                .help("Trace time from which to load records (default: the beginning)"),
+        )
+        .arg(
+            Arg::with_name("end")
+                .long("end")
+                .value_name("SECONDS")
+                .help("Trace time up to which to load records (default: the end)"),
+        )
+        .get_matches();
+
+    let trace_path = args.value_of("trace-path").unwrap();
+    let tables: Vec<&str> = match args.values_of("table") {
+        Some(t) => t.collect(),
+        None => TABLES.to_vec(),
+    };
+    let start = seconds(&args, "start").unwrap_or(0);
+    let end = seconds(&args, "end").unwrap_or(u64::MAX);
+
+    let mut db = Database::open(args.value_of("database").unwrap())?;
+    for table in tables {
+        match table {
+            "machine_events" => load::<MachineEvent>(&mut db, trace_path, start, end)?,
+            "machine_attributes" => load::<MachineAttribute>(&mut db, trace_path, start, end)?,
+            "job_events" => load::<JobEvent>(&mut db, trace_path, start, end)?,
+            "task_events" => load::<TaskEvent>(&mut db, trace_path, start, end)?,
+            "task_constraints" => load::<TaskConstraint>(&mut db, trace_path, start, end)?,
+            "task_usage" => load::<TaskUsageRecord>(&mut db, trace_path, start, end)?,
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
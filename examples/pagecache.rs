@@ -1,26 +1,20 @@
 extern crate gctu;
 
+use gctu::cluster_state::ClusterState;
 use gctu::common::{self, TRACE_START_TIME};
-use gctu::job_events::{JobEventIterator, JobEventType};
-use gctu::machine_events::{MachineEventIterator, MachineEventType};
+use gctu::distribution::Distribution;
+use gctu::merge::{MergedEventIterator, Table};
 use gctu::task_usage::TaskUsageIterator;
-use hdrhistogram::Histogram;
 use std::collections::HashMap;
 
-fn print(label: &str, hist: &hdrhistogram::Histogram<u64>) {
-    println!("\n# {} samples: {}", label, hist.len());
-    println!("median: {}", hist.value_at_quantile(0.5) as f64 / 10000.0);
-    println!(
-        "90th percentile: {}",
-        hist.value_at_quantile(0.9) as f64 / 10000.0
-    );
-    println!(
-        "99th percentile: {}",
-        hist.value_at_quantile(0.99) as f64 / 10000.0
-    );
+fn print(label: &str, dist: &Distribution) {
+    println!("\n# {} samples: {}", label, dist.len());
+    println!("median: {}", dist.value_at_quantile(0.5));
+    println!("90th percentile: {}", dist.value_at_quantile(0.9));
+    println!("99th percentile: {}", dist.value_at_quantile(0.99));
 }
 
-fn main() -> std::io::Result<()> {
+fn main() -> Result<(), gctu::Error> {
     use clap::{App, Arg};
 
     let args = App::new("pagecache")
@@ -58,77 +52,50 @@ fn main() -> std::io::Result<()> {
         unimplemented!("processing beyond the initial state is not currently supported");
     }
 
-    let mut canon = Histogram::<u64>::new_with_bounds(1, 10000, 2).unwrap();
-    let mut assigned = Histogram::<u64>::new_with_bounds(1, 10000, 2).unwrap();
-    let mut unmapped_pc = Histogram::<u64>::new_with_bounds(1, 10000, 2).unwrap();
-    let mut total_pc = Histogram::<u64>::new_with_bounds(1, 10000, 2).unwrap();
-
-    let mut active_jobs = HashMap::new();
-    let mut active_machines = HashMap::new();
-
-    let job_iter = JobEventIterator::new(trace_path);
-
-    for rec in job_iter {
-        let job_event = rec.expect("failed to parse job event!");
-        if initial_only && job_event.time > TRACE_START_TIME {
-            break;
-        }
-
-        if job_event.event_type == JobEventType::Submit {
-            active_jobs.insert(job_event.job_id, job_event);
-        }
-    }
-
-    let machine_event_iter = MachineEventIterator::new(trace_path);
-    for rec in machine_event_iter {
-        let machine_event = rec.expect("failed to parse machine event!");
-        if initial_only && machine_event.time > TRACE_START_TIME {
-            break;
-        }
-
-        if machine_event.event_type == MachineEventType::Add {
-            if let Some(mf) = machine_event.memory {
-                active_machines.insert(machine_event.machine_id, mf);
-            }
-        }
+    let mut canon = Distribution::default();
+    let mut assigned = Distribution::default();
+    let mut unmapped_pc = Distribution::default();
+    let mut total_pc = Distribution::default();
+
+    // the initial state comprises all events up to and including TRACE_START_TIME
+    let end_time = if initial_only {
+        TRACE_START_TIME + 1
+    } else {
+        u64::MAX
+    };
+
+    // only machine capacities and job scheduling classes are needed, so the other tables
+    // `ClusterState::at` replays are skipped (and need not be present)
+    let mut initial_state = ClusterState::new();
+    let state_tables = [Table::MachineEvents, Table::JobEvents];
+    for ev in MergedEventIterator::range(trace_path, &state_tables, 0, TRACE_START_TIME + 1)? {
+        initial_state.apply(&ev?);
     }
 
     let mut pcache_by_machine = HashMap::new();
     let mut pcache_by_job = HashMap::new();
 
-    let usage_iter = TaskUsageIterator::new(trace_path);
+    let usage_iter = TaskUsageIterator::range(trace_path, 0, end_time)?;
 
     for rec in usage_iter {
         let task_usage = rec.as_ref().expect("failed to parse task record!");
-        if initial_only && task_usage.start_time > TRACE_START_TIME {
-            // stop iterating once we're no longer interested
-            break;
-        }
-
-        if let Some(mem_frac) = active_machines.get(&task_usage.machine_id) {
+        let machine = initial_state.machines.get(&task_usage.machine_id);
+        if let Some(mem_frac) = machine.and_then(|m| m.memory) {
             if let Some(cmu) = task_usage.canonical_mem_usage {
                 let cmu = cmu / mem_frac;
-                canon
-                    .record((cmu * 10000.0) as u64)
-                    .expect("recording to histogram failed");
+                canon.record(cmu);
             }
             if let Some(amu) = task_usage.assigned_mem_usage {
                 let amu = amu / mem_frac;
-                assigned
-                    .record((amu * 10000.0) as u64)
-                    .expect("recording to histogram failed");
+                assigned.record(amu);
             }
             if let Some(upc) = task_usage.unmapped_page_cache {
                 let upc = upc / mem_frac;
-                unmapped_pc
-                    .record((upc * 10000.0) as u64)
-                    .expect("recording to histogram failed");
+                unmapped_pc.record(upc);
             }
             if let Some(tpc) = task_usage.total_page_cache {
                 let tpc = tpc / mem_frac;
-                total_pc
-                    .record((tpc * 10000.0) as u64)
-                    .expect("recording to histogram failed");
+                total_pc.record(tpc);
                 let tpc_on_machine = pcache_by_machine
                     .entry(task_usage.machine_id)
                     .or_insert(0.0);
@@ -156,12 +123,11 @@ fn main() -> std::io::Result<()> {
     println!("job ID, scheduling class, number of tasks, avg. page cache use");
     for (j, (tasks, tpc)) in pcache_by_job {
         let sum: f64 = tpc.iter().sum();
-        if let Some(j) = active_jobs.get(&j) {
-            let class = j
+        if let Some(job) = initial_state.jobs.get(&j) {
+            let class = job
                 .scheduling_class
-                .as_ref()
-                .unwrap_or(&common::SchedulingClass::Unknown);
-            println!("{},{:?},{},{}", j.job_id, class, tasks, sum / tasks as f64);
+                .unwrap_or(common::SchedulingClass::Unknown);
+            println!("{},{:?},{},{}", j, class, tasks, sum / tasks as f64);
         } else {
             eprintln!("skipping unknown job {}", j);
         }
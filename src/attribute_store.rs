@@ -0,0 +1,175 @@
+use crate::common::AttributeValue;
+use crate::error::Error;
+use crate::machine_attributes::{MachineAttribute, MachineAttributeIterator};
+use std::collections::HashMap;
+
+/// A machine's attributes at some point in time, by name. Attributes may be present without a
+/// value.
+pub type AttributeMap<'a> = HashMap<&'a str, Option<&'a AttributeValue>>;
+
+/// A change to an attribute: it is either set (possibly without a value) or deleted.
+type AttributeStep = (u64, Option<Option<AttributeValue>>);
+
+/// The history of every machine's attributes, built from the machine attributes table, which
+/// answers what a machine's attributes were at any time.
+#[derive(Clone, Debug, Default)]
+pub struct AttributeStore {
+    machines: HashMap<u64, HashMap<String, Vec<AttributeStep>>>,
+}
+
+impl AttributeStore {
+    /// Builds the store from the machine attributes of the trace at `trace_path`.
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        Self::from_attributes(MachineAttributeIterator::new(trace_path)?)
+    }
+
+    /// Builds the store from machine attribute records in time order.
+    pub fn from_attributes<I>(attributes: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Result<MachineAttribute, Error>>,
+    {
+        let mut store = AttributeStore::default();
+        for a in attributes {
+            store.add(a?);
+        }
+        Ok(store)
+    }
+
+    fn add(&mut self, a: MachineAttribute) {
+        let steps = self
+            .machines
+            .entry(a.machine_id)
+            .or_default()
+            .entry(a.attribute_name)
+            .or_default();
+        let value = if a.attribute_deleted {
+            None
+        } else {
+            Some(a.attribute_value)
+        };
+        // a later record at the same timestamp overwrites an earlier one
+        match steps.last_mut() {
+            Some((t, v)) if *t == a.time => *v = value,
+            _ => steps.push((a.time, value)),
+        }
+    }
+
+    /// The IDs of all machines that have attributes.
+    pub fn machine_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.machines.keys().cloned()
+    }
+
+    /// A machine's attribute at `time`: `None` if the attribute was absent, and `Some(None)` if it
+    /// was present without a value.
+    pub fn attribute_at(
+        &self,
+        machine_id: u64,
+        name: &str,
+        time: u64,
+    ) -> Option<Option<&AttributeValue>> {
+        let steps = self.machines.get(&machine_id)?.get(name)?;
+        Self::value_at(steps, time)
+    }
+
+    fn value_at(steps: &[AttributeStep], time: u64) -> Option<Option<&AttributeValue>> {
+        let i = steps.partition_point(|(t, _)| *t <= time);
+        steps[..i]
+            .last()
+            .and_then(|(_, v)| v.as_ref())
+            .map(|v| v.as_ref())
+    }
+
+    /// All of a machine's attributes at `time`.
+    pub fn attributes_at(&self, machine_id: u64, time: u64) -> AttributeMap<'_> {
+        self.machines
+            .get(&machine_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, steps)| Self::value_at(steps, time).map(|v| (name.as_str(), v)))
+            .collect()
+    }
+
+    /// The machines whose attributes at `time` satisfy `predicate`, in ascending ID order. Only
+    /// machines that appear in the attributes table are considered.
+    pub fn machines_matching<F>(&self, time: u64, predicate: F) -> Vec<u64>
+    where
+        F: Fn(&AttributeMap) -> bool,
+    {
+        let mut machines: Vec<u64> = self
+            .machines
+            .keys()
+            .filter(|m| predicate(&self.attributes_at(**m, time)))
+            .cloned()
+            .collect();
+        machines.sort_unstable();
+        machines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse;
+
+    fn store(lines: &[&str]) -> AttributeStore {
+        AttributeStore::from_attributes(lines.iter().map(|l| Ok(parse(l)))).unwrap()
+    }
+
+    fn hash(s: &str) -> AttributeValue {
+        AttributeValue::Hash(s.to_string())
+    }
+
+    #[test]
+    fn absent_and_valueless_attributes_differ() {
+        let s = store(&["10,1,rack,r1,0", "10,1,ssd,,0"]);
+        assert_eq!(s.attribute_at(1, "rack", 9), None);
+        assert_eq!(s.attribute_at(1, "rack", 10), Some(Some(&hash("r1"))));
+        assert_eq!(s.attribute_at(1, "ssd", 10), Some(None));
+        assert_eq!(s.attribute_at(1, "gpu", 10), None);
+        assert_eq!(s.attribute_at(2, "rack", 10), None);
+    }
+
+    #[test]
+    fn deleted_attributes_can_be_set_again() {
+        let s = store(&[
+            "0,1,kernel,5,0",
+            "10,1,kernel,,1",
+            "20,1,kernel,6,0",
+            // a later record at the same timestamp wins
+            "30,1,kernel,7,0",
+            "30,1,kernel,8,0",
+            "40,1,kernel,,1",
+            "40,1,kernel,9,0",
+        ]);
+        let values: Vec<_> = [0, 9, 10, 19, 20, 30, 40]
+            .iter()
+            .map(|t| s.attribute_at(1, "kernel", *t).flatten().cloned())
+            .collect();
+        let int = |i| Some(AttributeValue::Integer(i));
+        assert_eq!(
+            values,
+            vec![int(5), int(5), None, None, int(6), int(8), int(9)]
+        );
+        assert_eq!(s.attribute_at(1, "kernel", 10), None);
+    }
+
+    #[test]
+    fn machines_match_their_attributes_at_the_time() {
+        let s = store(&[
+            "0,3,rack,r1,0",
+            "0,1,rack,r1,0",
+            "0,2,rack,r2,0",
+            "0,2,ssd,,0",
+            "10,1,rack,,1",
+            "10,3,ssd,,0",
+        ]);
+        let in_r1 = |m: &AttributeMap| m.get("rack") == Some(&Some(&hash("r1")));
+        assert_eq!(s.machines_matching(0, in_r1), vec![1, 3]);
+        assert_eq!(s.machines_matching(10, in_r1), vec![3]);
+        let has_ssd = |m: &AttributeMap| m.contains_key("ssd");
+        assert_eq!(s.machines_matching(0, has_ssd), vec![2]);
+        assert_eq!(s.machines_matching(10, has_ssd), vec![2, 3]);
+        assert_eq!(s.attributes_at(1, 10).len(), 0);
+        assert_eq!(s.attributes_at(3, 10).len(), 2);
+    }
+}
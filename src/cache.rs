@@ -0,0 +1,1362 @@
+use crate::common::{AttributeValue, TraceRecord};
+use crate::error::Error;
+use crate::job_events::JobEvent;
+use crate::machine_attributes::MachineAttribute;
+use crate::machine_events::MachineEvent;
+use crate::schema::{self, Column, ColumnType, RowError, Value};
+use crate::task_constraints::TaskConstraint;
+use crate::task_events::TaskEvent;
+use crate::task_usage::TaskUsageRecord;
+use serde::de::{self, DeserializeOwned, IntoDeserializer};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::slice;
+
+// A cache file holds one table. It starts with a header (magic, version, table name and the
+// columns of the record struct), followed by blocks of rows and an index of the blocks. Each block
+// stores its columns one after another: an optional bitmap of non-null rows, then the non-null
+// values as integers, bit-packed as their difference to the block's smallest one. Integer columns
+// store the difference to the previous value. Float columns store the values scaled by a power of
+// ten, if all of the block's values are recovered exactly from that (the trace prints them with
+// few decimal digits), and their bits otherwise. String columns store indices into the block's
+// distinct strings. Nothing is compressed further, as inflating the columns would take longer
+// than the rest of the read. The file ends with the index's offset and the magic again.
+//
+// Reading decodes the values row by row, straight from the block, and builds records from them
+// with their `CacheRecord` implementations rather than through serde.
+
+const MAGIC: &[u8; 4] = b"GCTU";
+const VERSION: u8 = 2;
+
+/// The most decimal digits by which a float column is scaled to integers.
+const MAX_DIGITS: u8 = 18;
+/// Marks a float column stored as raw bits, in place of its number of digits.
+const RAW_FLOATS: u8 = 0xff;
+/// The most bits a packed value takes, other than 64.
+const MAX_PACKED_BITS: u32 = 56;
+
+/// The number of rows per block unless configured otherwise.
+pub static DEFAULT_BLOCK_ROWS: usize = 65_536;
+
+/// The cache file for a table in `cache_dir`.
+pub fn cache_path<T: TraceRecord>(cache_dir: &str) -> PathBuf {
+    Path::new(cache_dir).join(format!("{}.gctu", T::TABLE))
+}
+
+/// Writes all `records` of a table to its cache file in `cache_dir`, returning the number of
+/// records written.
+pub fn write_cache<T, I>(cache_dir: &str, records: I) -> Result<u64, Error>
+where
+    T: TraceRecord + Serialize,
+    I: IntoIterator<Item = Result<T, Error>>,
+{
+    let mut writer = CacheWriter::create(cache_dir)?;
+    for r in records {
+        writer.write(&r?)?;
+    }
+    writer.finish()
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BlockInfo {
+    offset: u64,
+    len: u64,
+    rows: u64,
+    min_time: u64,
+    max_time: u64,
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn get_varint(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    // most deltas fit in a byte
+    let b = *buf.get(*pos)?;
+    if b < 0x80 {
+        *pos += 1;
+        return Some(u64::from(b));
+    }
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *buf.get(*pos)?;
+        *pos += 1;
+        v |= u64::from(b & 0x7f) << shift;
+        if b < 0x80 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+fn zigzag(i: i64) -> u64 {
+    ((i << 1) ^ (i >> 63)) as u64
+}
+
+fn unzigzag(u: u64) -> i64 {
+    (u >> 1) as i64 ^ -((u & 1) as i64)
+}
+
+fn put_bitmap(buf: &mut Vec<u8>, bits: impl Iterator<Item = bool>) {
+    let mut byte = 0u8;
+    let mut n = 0;
+    for bit in bits {
+        byte |= (bit as u8) << (n % 8);
+        n += 1;
+        if n % 8 == 0 {
+            buf.push(byte);
+            byte = 0;
+        }
+    }
+    if n % 8 != 0 {
+        buf.push(byte);
+    }
+}
+
+fn get_u64(buf: &[u8], pos: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[pos..pos + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Writes `values` as their differences to the smallest of them, packed into as many bits as the
+/// largest difference needs. Values wider than `MAX_PACKED_BITS` take 64 bits, which keeps them
+/// byte-aligned, so that every value can be read from a single (unaligned) `u64`.
+fn put_packed(buf: &mut Vec<u8>, values: &[i64]) {
+    let min = values.iter().copied().min().unwrap_or(0);
+    let offsets = values.iter().map(|v| (*v as u64).wrapping_sub(min as u64));
+    let bits = match 64 - offsets.clone().max().unwrap_or(0).leading_zeros() {
+        bits if bits > MAX_PACKED_BITS => 64,
+        bits => bits,
+    };
+    put_varint(buf, zigzag(min));
+    buf.push(bits as u8);
+    let start = buf.len();
+    // padded so that reading the last value's `u64` stays within the column
+    buf.resize(start + (values.len() * bits as usize).div_ceil(8) + 8, 0);
+    for (i, offset) in offsets.enumerate().filter(|_| bits > 0) {
+        let bit = i * bits as usize;
+        let pos = start + bit / 8;
+        let word = get_u64(buf, pos) | offset << (bit % 8);
+        buf[pos..pos + 8].copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+/// `n` values written by `put_packed`, which start at `start` in a block.
+#[derive(Clone, Copy, Debug)]
+struct Packed {
+    min: u64,
+    bits: u32,
+    mask: u64,
+    start: usize,
+}
+
+impl Packed {
+    /// Reads the header of `n` packed values at `pos` in `block`, moving `pos` past them.
+    fn read(block: &[u8], pos: &mut usize, n: usize) -> Option<Self> {
+        let min = unzigzag(get_varint(block, pos)?) as u64;
+        let bits = u32::from(*block.get(*pos)?);
+        if bits > MAX_PACKED_BITS && bits != 64 {
+            return None;
+        }
+        let start = *pos + 1;
+        *pos = start + n.checked_mul(bits as usize)?.div_ceil(8) + 8;
+        if *pos > block.len() {
+            return None;
+        }
+        let mask = match bits {
+            0 => 0,
+            bits => u64::MAX >> (64 - bits),
+        };
+        Some(Packed {
+            min,
+            bits,
+            mask,
+            start,
+        })
+    }
+
+    /// The `i`th value, which must be one of the `n` that were read.
+    fn get(&self, block: &[u8], i: usize) -> u64 {
+        let bit = i * self.bits as usize;
+        let word = get_u64(block, self.start + bit / 8) >> (bit % 8);
+        self.min.wrapping_add(word & self.mask)
+    }
+}
+
+/// `v` times `10^digits`, if that is an integer from which dividing by `10^digits` gives back
+/// exactly `v`.
+fn scale_float(v: f64, digits: u8) -> Option<i64> {
+    let scale = 10f64.powi(i32::from(digits));
+    // the check covers values that do not fit, as the conversion saturates
+    let scaled = (v * scale).round() as i64;
+    if (scaled as f64 / scale).to_bits() == v.to_bits() {
+        Some(scaled)
+    } else {
+        None
+    }
+}
+
+fn encode_column(column: &Column, values: &[&Value]) -> Result<Vec<u8>, String> {
+    let mismatch = || format!("a value does not match column `{}`", column.name);
+    let mut buf = Vec::new();
+    if column.nullable {
+        put_bitmap(&mut buf, values.iter().map(|v| **v != Value::Null));
+    }
+    let present = values.iter().filter(|v| ***v != Value::Null);
+    match column.column_type {
+        ColumnType::Bool => {
+            let bits: Vec<i64> = present
+                .map(|v| match v {
+                    Value::Bool(b) => Ok(*b as i64),
+                    _ => Err(mismatch()),
+                })
+                .collect::<Result<_, String>>()?;
+            put_packed(&mut buf, &bits);
+        }
+        ColumnType::U8 | ColumnType::U64 | ColumnType::I64 => {
+            let mut prev = 0u64;
+            let deltas: Vec<i64> = present
+                .map(|v| {
+                    let v = match v {
+                        Value::UInt(u) => *u,
+                        Value::Int(i) => *i as u64,
+                        _ => return Err(mismatch()),
+                    };
+                    let delta = v.wrapping_sub(prev) as i64;
+                    prev = v;
+                    Ok(delta)
+                })
+                .collect::<Result<_, String>>()?;
+            put_packed(&mut buf, &deltas);
+        }
+        ColumnType::F64 => {
+            let floats: Vec<f64> = present
+                .map(|v| match v {
+                    Value::Float(f) => Ok(*f),
+                    _ => Err(mismatch()),
+                })
+                .collect::<Result<_, String>>()?;
+            // the fewest digits that suffice for every value, if some do
+            let digits = floats.iter().try_fold(0, |digits, f| {
+                (digits..=MAX_DIGITS).find(|d| scale_float(*f, *d).is_some())
+            });
+            let scaled = digits.and_then(|d| {
+                let scaled: Option<Vec<i64>> = floats.iter().map(|f| scale_float(*f, d)).collect();
+                Some((d, scaled?))
+            });
+            match scaled {
+                Some((digits, scaled)) => {
+                    buf.push(digits);
+                    put_packed(&mut buf, &scaled);
+                }
+                None => {
+                    buf.push(RAW_FLOATS);
+                    let bits: Vec<i64> = floats.iter().map(|f| f.to_bits() as i64).collect();
+                    put_packed(&mut buf, &bits);
+                }
+            }
+        }
+        ColumnType::Str => {
+            let mut indices = HashMap::new();
+            let mut strings = Vec::new();
+            let mut rows = Vec::new();
+            for v in present {
+                match v {
+                    Value::Str(s) => {
+                        let next = strings.len() as i64;
+                        rows.push(*indices.entry(s.as_str()).or_insert_with(|| {
+                            strings.push(s.as_str());
+                            next
+                        }));
+                    }
+                    _ => return Err(mismatch()),
+                }
+            }
+            put_varint(&mut buf, strings.len() as u64);
+            for s in strings {
+                put_varint(&mut buf, s.len() as u64);
+                buf.extend_from_slice(s.as_bytes());
+            }
+            put_packed(&mut buf, &rows);
+        }
+    }
+    Ok(buf)
+}
+
+/// How a column of a block stores its non-null values, all of which are packed integers.
+enum Encoding {
+    /// 0 or 1.
+    Bool,
+    /// The difference to the previous value.
+    Int,
+    /// The values multiplied by the given power of ten.
+    ScaledFloat(f64),
+    /// The values' bits.
+    RawFloat,
+    /// Indices into the block's distinct strings, which are given concatenated, with each one's
+    /// range of bytes in them.
+    Str(String, Vec<Range<usize>>),
+}
+
+/// A column of the current block, whose values are decoded as its rows are read, in order.
+struct ColumnCursor {
+    /// Where the bitmap of non-null rows starts in the block, for nullable columns.
+    present: Option<usize>,
+    encoding: Encoding,
+    values: Packed,
+    /// The index of the next non-null value.
+    next: usize,
+    /// The previous integer, for `Encoding::Int`.
+    prev: u64,
+}
+
+impl ColumnCursor {
+    /// Opens the column of a block of `rows` rows that is stored at `range` of `block`.
+    fn new(column: &Column, block: &[u8], range: Range<usize>, rows: usize) -> Option<Self> {
+        let buf = block.get(..range.end)?;
+        let mut pos = range.start;
+        let (present, n) = if column.nullable {
+            let bits = buf.get(pos..pos + rows.div_ceil(8))?;
+            // the bitmap's last byte is padded with zeros
+            let n = bits.iter().map(|b| b.count_ones() as usize).sum();
+            pos += bits.len();
+            (Some(range.start), n)
+        } else {
+            (None, rows)
+        };
+        let encoding = match column.column_type {
+            ColumnType::Bool => Encoding::Bool,
+            ColumnType::U8 | ColumnType::U64 | ColumnType::I64 => Encoding::Int,
+            ColumnType::F64 => {
+                let digits = *buf.get(pos)?;
+                pos += 1;
+                if digits == RAW_FLOATS {
+                    Encoding::RawFloat
+                } else if digits <= MAX_DIGITS {
+                    Encoding::ScaledFloat(10f64.powi(i32::from(digits)))
+                } else {
+                    return None;
+                }
+            }
+            ColumnType::Str => {
+                let mut strings = String::new();
+                let mut ranges = Vec::new();
+                for _ in 0..get_varint(buf, &mut pos)? {
+                    let len = get_varint(buf, &mut pos)? as usize;
+                    strings.push_str(std::str::from_utf8(buf.get(pos..pos + len)?).ok()?);
+                    pos += len;
+                    ranges.push(strings.len() - len..strings.len());
+                }
+                Encoding::Str(strings, ranges)
+            }
+        };
+        let values = Packed::read(buf, &mut pos, n)?;
+        if pos != range.end {
+            return None;
+        }
+        Some(ColumnCursor {
+            present,
+            encoding,
+            values,
+            next: 0,
+            prev: 0,
+        })
+    }
+
+    /// Whether `row` has a value.
+    fn has_value(&self, block: &[u8], row: usize) -> bool {
+        self.present
+            .is_none_or(|start| block[start + row / 8] & (1 << (row % 8)) != 0)
+    }
+
+    /// Moves past the next non-null value, returning it.
+    fn value(&mut self, block: &[u8]) -> u64 {
+        self.next += 1;
+        self.values.get(block, self.next - 1)
+    }
+
+    /// The next non-null value of an integer column, without moving past it.
+    fn peek_int(&self, block: &[u8]) -> u64 {
+        self.prev.wrapping_add(self.values.get(block, self.next))
+    }
+
+    fn int(&mut self, block: &[u8]) -> u64 {
+        self.prev = self.peek_int(block);
+        self.next += 1;
+        self.prev
+    }
+
+    /// Moves past `row`, which must be the row after the one last read.
+    fn skip(&mut self, block: &[u8], row: usize) {
+        if self.has_value(block, row) {
+            match self.encoding {
+                Encoding::Int => self.int(block),
+                _ => self.value(block),
+            };
+        }
+    }
+}
+
+/// Writes a table's records to a cache file.
+///
+/// The file is written under a temporary name and moved into place by `finish`, so an
+/// interrupted write never leaves a cache file that looks complete.
+pub struct CacheWriter<T> {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    file: BufWriter<File>,
+    offset: u64,
+    columns: Vec<Column>,
+    block_rows: usize,
+    rows: Vec<Vec<Value>>,
+    /// The earliest and latest timestamps in the pending block.
+    times: Option<(u64, u64)>,
+    index: Vec<BlockInfo>,
+    written: u64,
+    _record: PhantomData<T>,
+}
+
+impl<T: TraceRecord + Serialize> CacheWriter<T> {
+    /// Creates the cache file for `T`'s table in `cache_dir`, creating the directory if needed.
+    pub fn create(cache_dir: &str) -> Result<Self, Error> {
+        Self::with_block_rows(cache_dir, DEFAULT_BLOCK_ROWS)
+    }
+
+    /// Like `create`, but with `block_rows` rows per block. Smaller blocks make time range reads
+    /// more selective, at the expense of compression.
+    pub fn with_block_rows(cache_dir: &str, block_rows: usize) -> Result<Self, Error> {
+        assert!(block_rows > 0, "blocks must hold at least one row");
+        let path = cache_path::<T>(cache_dir);
+        let tmp_path = path.with_extension("gctu.tmp");
+        fs::create_dir_all(cache_dir).map_err(|e| Error::io(Path::new(cache_dir), e))?;
+        let columns = schema::columns::<T>().map_err(|e| Error::cache(&path, e.to_string()))?;
+        let file = File::create(&tmp_path).map_err(|e| Error::io(&tmp_path, e))?;
+
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION);
+        put_varint(&mut header, T::TABLE.len() as u64);
+        header.extend_from_slice(T::TABLE.as_bytes());
+        put_varint(&mut header, columns.len() as u64);
+        for c in &columns {
+            put_varint(&mut header, c.name.len() as u64);
+            header.extend_from_slice(c.name.as_bytes());
+            header.push(c.column_type as u8);
+            header.push(c.nullable as u8);
+        }
+        let mut writer = CacheWriter {
+            path,
+            tmp_path,
+            file: BufWriter::new(file),
+            offset: 0,
+            columns,
+            block_rows,
+            rows: Vec::with_capacity(block_rows),
+            times: None,
+            index: Vec::new(),
+            written: 0,
+            _record: PhantomData,
+        };
+        writer.put(&header)?;
+        Ok(writer)
+    }
+
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.file
+            .write_all(bytes)
+            .map_err(|e| Error::io(&self.tmp_path, e))?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    pub fn write(&mut self, record: &T) -> Result<(), Error> {
+        let row = schema::to_row(record).map_err(|e| Error::cache(&self.path, e.to_string()))?;
+        let time = record.time();
+        self.times = Some(match self.times {
+            Some((min, max)) => (min.min(time), max.max(time)),
+            None => (time, time),
+        });
+        self.rows.push(row);
+        self.written += 1;
+        if self.rows.len() == self.block_rows {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> Result<(), Error> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let mut block = Vec::new();
+        for (i, c) in self.columns.iter().enumerate() {
+            let values: Vec<&Value> = self.rows.iter().map(|r| &r[i]).collect();
+            let encoded = encode_column(c, &values).map_err(|e| Error::cache(&self.path, e))?;
+            put_varint(&mut block, encoded.len() as u64);
+            block.extend_from_slice(&encoded);
+        }
+        self.index.push(BlockInfo {
+            offset: self.offset,
+            len: block.len() as u64,
+            rows: self.rows.len() as u64,
+            min_time: self.times.map_or(0, |t| t.0),
+            max_time: self.times.map_or(0, |t| t.1),
+        });
+        self.put(&block)?;
+        self.rows.clear();
+        self.times = None;
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the block index, and moves the file into place. Returns the
+    /// number of records written.
+    pub fn finish(mut self) -> Result<u64, Error> {
+        self.flush_block()?;
+        let index_offset = self.offset;
+        let mut footer = Vec::new();
+        put_varint(&mut footer, self.index.len() as u64);
+        for b in &self.index {
+            for v in &[b.offset, b.len, b.rows, b.min_time, b.max_time] {
+                put_varint(&mut footer, *v);
+            }
+        }
+        footer.extend_from_slice(&index_offset.to_le_bytes());
+        footer.extend_from_slice(MAGIC);
+        self.put(&footer)?;
+        self.file
+            .flush()
+            .map_err(|e| Error::io(&self.tmp_path, e))?;
+        fs::rename(&self.tmp_path, &self.path).map_err(|e| Error::io(&self.path, e))?;
+        Ok(self.written)
+    }
+}
+
+/// Iterates over the records in a table's cache file, in the order they were written.
+///
+/// A record's first field must be the timestamp that `TraceRecord::time` returns, which it is for
+/// all of the trace's tables, and the record type must implement `CacheRecord`.
+pub struct CacheIterator<T> {
+    path: PathBuf,
+    file: BufReader<File>,
+    columns: Vec<Column>,
+    blocks: Vec<BlockInfo>,
+    next_block: usize,
+    /// The current block and its columns.
+    block: Vec<u8>,
+    cursors: Vec<ColumnCursor>,
+    rows: usize,
+    row: usize,
+    range: Range<u64>,
+    done: bool,
+    _record: PhantomData<T>,
+}
+
+fn read_varint<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut b = [0u8];
+        r.read_exact(&mut b)?;
+        v |= u64::from(b[0] & 0x7f) << shift;
+        if b[0] < 0x80 {
+            return Ok(v);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "invalid varint"))
+}
+
+fn read_string<R: Read>(r: &mut R) -> io::Result<String> {
+    let len = read_varint(r)? as usize;
+    let mut bytes = vec![0u8; len];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl<T: TraceRecord> CacheIterator<T> {
+    /// Opens the cache file for `T`'s table in `cache_dir`.
+    pub fn new(cache_dir: &str) -> Result<Self, Error> {
+        Self::range(cache_dir, 0, u64::MAX)
+    }
+
+    /// Iterates over the records with timestamps in `[start, end)`, skipping blocks whose
+    /// records all lie outside the range.
+    pub fn range(cache_dir: &str, start: u64, end: u64) -> Result<Self, Error> {
+        let path = cache_path::<T>(cache_dir);
+        let file = File::open(&path).map_err(|e| Error::io(&path, e))?;
+        let mut file = BufReader::new(file);
+        let columns = schema::columns::<T>().map_err(|e| Error::cache(&path, e.to_string()))?;
+        if columns[0].column_type != ColumnType::U64 || columns[0].nullable {
+            let msg = format!("the first field of a {} record is not its time", T::TABLE);
+            return Err(Error::cache(&path, msg));
+        }
+        let blocks = match Self::read_index(&mut file, &columns) {
+            Ok(blocks) => blocks,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(Error::cache(&path, "truncated cache file".to_string()))
+            }
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                return Err(Error::cache(&path, e.to_string()))
+            }
+            Err(e) => return Err(Error::io(&path, e)),
+        };
+        let blocks = blocks
+            .into_iter()
+            .filter(|b| b.max_time >= start && b.min_time < end)
+            .collect();
+        Ok(CacheIterator {
+            path,
+            file,
+            columns,
+            blocks,
+            next_block: 0,
+            block: Vec::new(),
+            cursors: Vec::new(),
+            rows: 0,
+            row: 0,
+            range: start..end,
+            done: false,
+            _record: PhantomData,
+        })
+    }
+
+    /// Checks the header against `T`'s columns and reads the block index.
+    fn read_index(file: &mut BufReader<File>, columns: &[Column]) -> io::Result<Vec<BlockInfo>> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut magic = [0u8; 5];
+        file.read_exact(&mut magic)?;
+        if &magic[..4] != MAGIC || magic[4] != VERSION {
+            return Err(invalid(
+                "not a gctu cache file of a supported version".into(),
+            ));
+        }
+        let table = read_string(file)?;
+        if table != T::TABLE {
+            return Err(invalid(format!(
+                "cache holds table {}, not {}",
+                table,
+                T::TABLE
+            )));
+        }
+        let n = read_varint(file)? as usize;
+        let mut matches = n == columns.len();
+        for i in 0..n {
+            let name = read_string(file)?;
+            let mut ty = [0u8; 2];
+            file.read_exact(&mut ty)?;
+            matches &= columns.get(i).is_some_and(|c| {
+                c.name == name && c.column_type as u8 == ty[0] && c.nullable as u8 == ty[1]
+            });
+        }
+        if !matches {
+            return Err(invalid(format!(
+                "cached columns do not match the {} record struct",
+                T::TABLE
+            )));
+        }
+
+        file.seek(SeekFrom::End(-12))?;
+        let mut trailer = [0u8; 12];
+        file.read_exact(&mut trailer)?;
+        if &trailer[8..] != MAGIC {
+            return Err(invalid("incomplete cache file".into()));
+        }
+        let mut offset = [0u8; 8];
+        offset.copy_from_slice(&trailer[..8]);
+        file.seek(SeekFrom::Start(u64::from_le_bytes(offset)))?;
+        let n = read_varint(file)?;
+        let mut blocks = Vec::new();
+        for _ in 0..n {
+            blocks.push(BlockInfo {
+                offset: read_varint(file)?,
+                len: read_varint(file)?,
+                rows: read_varint(file)?,
+                min_time: read_varint(file)?,
+                max_time: read_varint(file)?,
+            });
+        }
+        Ok(blocks)
+    }
+
+    /// Reads and decodes the next block; returns `false` if there are none left.
+    fn load_block(&mut self) -> Result<bool, Error> {
+        let block = match self.blocks.get(self.next_block) {
+            Some(b) => *b,
+            None => return Ok(false),
+        };
+        self.next_block += 1;
+        self.block.resize(block.len as usize, 0);
+        self.file
+            .seek(SeekFrom::Start(block.offset))
+            .and_then(|_| self.file.read_exact(&mut self.block))
+            .map_err(|e| Error::io(&self.path, e))?;
+
+        let corrupt = || Error::cache(&self.path, format!("corrupt block at {}", block.offset));
+        let rows = block.rows as usize;
+        let mut pos = 0;
+        let mut cursors = Vec::with_capacity(self.columns.len());
+        for c in &self.columns {
+            let len = get_varint(&self.block, &mut pos).ok_or_else(corrupt)? as usize;
+            let cursor = ColumnCursor::new(c, &self.block, pos..pos + len, rows);
+            cursors.push(cursor.ok_or_else(corrupt)?);
+            pos += len;
+        }
+        self.cursors = cursors;
+        self.rows = rows;
+        self.row = 0;
+        Ok(true)
+    }
+}
+
+fn row_error(msg: &str) -> RowError {
+    de::Error::custom(msg)
+}
+
+/// `value`, which must not be null.
+fn required<V>(value: Option<V>) -> Result<V, RowError> {
+    value.ok_or_else(|| row_error("a null value in a column of a non-optional field"))
+}
+
+/// A row of a cache block, whose values are read one field after another, in column order. The
+/// `opt_*` methods read optional fields; the others fail if the value is null.
+pub struct CacheRow<'a> {
+    block: &'a [u8],
+    columns: slice::IterMut<'a, ColumnCursor>,
+    row: usize,
+}
+
+impl<'a> CacheRow<'a> {
+    /// Reads the next column's value with `get`, unless it is null. `get` returns `None` if the
+    /// column's encoding does not match the field's type.
+    fn next_value<V>(
+        &mut self,
+        get: impl FnOnce(&'a mut ColumnCursor, &'a [u8]) -> Option<V>,
+    ) -> Result<Option<V>, RowError> {
+        let column = self
+            .columns
+            .next()
+            .ok_or_else(|| row_error("more fields than columns"))?;
+        if !column.has_value(self.block, self.row) {
+            return Ok(None);
+        }
+        get(column, self.block)
+            .map(Some)
+            .ok_or_else(|| row_error("a field does not match its column"))
+    }
+
+    pub fn opt_bool(&mut self) -> Result<Option<bool>, RowError> {
+        self.next_value(|c, block| match c.encoding {
+            Encoding::Bool => Some(c.value(block) != 0),
+            _ => None,
+        })
+    }
+
+    pub fn opt_u8(&mut self) -> Result<Option<u8>, RowError> {
+        self.next_value(|c, block| match c.encoding {
+            Encoding::Int => u8::try_from(c.int(block)).ok(),
+            _ => None,
+        })
+    }
+
+    pub fn opt_u64(&mut self) -> Result<Option<u64>, RowError> {
+        self.next_value(|c, block| match c.encoding {
+            Encoding::Int => Some(c.int(block)),
+            _ => None,
+        })
+    }
+
+    pub fn opt_i64(&mut self) -> Result<Option<i64>, RowError> {
+        self.next_value(|c, block| match c.encoding {
+            Encoding::Int => Some(c.int(block) as i64),
+            _ => None,
+        })
+    }
+
+    pub fn opt_f64(&mut self) -> Result<Option<f64>, RowError> {
+        self.next_value(|c, block| match c.encoding {
+            Encoding::ScaledFloat(scale) => Some(c.value(block) as i64 as f64 / scale),
+            Encoding::RawFloat => Some(f64::from_bits(c.value(block))),
+            _ => None,
+        })
+    }
+
+    pub fn opt_str(&mut self) -> Result<Option<&'a str>, RowError> {
+        self.next_value(|c, block| {
+            let i = c.value(block) as usize;
+            match &c.encoding {
+                Encoding::Str(strings, ranges) => Some(&strings[ranges.get(i)?.clone()]),
+                _ => None,
+            }
+        })
+    }
+
+    /// Reads an enum encoded as a small integer.
+    pub fn opt_enum<E: DeserializeOwned>(&mut self) -> Result<Option<E>, RowError> {
+        self.opt_u8()?
+            .map(|v| E::deserialize(v.into_deserializer()))
+            .transpose()
+    }
+
+    pub fn bool(&mut self) -> Result<bool, RowError> {
+        required(self.opt_bool()?)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, RowError> {
+        required(self.opt_u8()?)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, RowError> {
+        required(self.opt_u64()?)
+    }
+
+    pub fn i64(&mut self) -> Result<i64, RowError> {
+        required(self.opt_i64()?)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, RowError> {
+        required(self.opt_f64()?)
+    }
+
+    pub fn str(&mut self) -> Result<&'a str, RowError> {
+        required(self.opt_str()?)
+    }
+
+    pub fn enum_value<E: DeserializeOwned>(&mut self) -> Result<E, RowError> {
+        required(self.opt_enum()?)
+    }
+}
+
+/// A record that can be read back from a cache file.
+///
+/// Records are built directly from the decoded columns instead of through their `Deserialize`
+/// implementations, which would take several times as long as the rest of the read.
+pub trait CacheRecord: TraceRecord {
+    /// Builds a record from `row`, reading one value per field, in field order.
+    fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError>;
+}
+
+impl<T: CacheRecord> Iterator for CacheIterator<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.row == self.rows {
+                match self.load_block() {
+                    Ok(true) => continue,
+                    Ok(false) => self.done = true,
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+                continue;
+            }
+            let row = self.row;
+            self.row += 1;
+            // rows outside the range are skipped by their time column, without building records
+            let block = &self.block;
+            let time = self.cursors[0].peek_int(block);
+            if !self.range.contains(&time) {
+                self.cursors.iter_mut().for_each(|c| c.skip(block, row));
+                continue;
+            }
+            let mut fields = CacheRow {
+                block,
+                columns: self.cursors.iter_mut(),
+                row,
+            };
+            let record = T::from_cache_row(&mut fields).and_then(|r| match fields.columns.next() {
+                Some(_) => Err(row_error("fewer fields than columns")),
+                None => Ok(r),
+            });
+            return match record {
+                Ok(record) => {
+                    debug_assert_eq!(time, record.time());
+                    Some(Ok(record))
+                }
+                Err(e) => {
+                    self.done = true;
+                    let offset = self.blocks[self.next_block - 1].offset;
+                    let msg = format!("block at {}: {}", offset, e);
+                    Some(Err(Error::cache(&self.path, msg)))
+                }
+            };
+        }
+        None
+    }
+}
+
+impl CacheRecord for JobEvent {
+    fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError> {
+        Ok(JobEvent {
+            time: row.u64()?,
+            missing_info: row.opt_enum()?,
+            job_id: row.u64()?,
+            event_type: row.enum_value()?,
+            user: row.opt_str()?.map(str::to_string),
+            scheduling_class: row.opt_enum()?,
+            job_name: row.opt_str()?.map(str::to_string),
+            logical_job_name: row.opt_str()?.map(str::to_string),
+        })
+    }
+}
+
+impl CacheRecord for TaskEvent {
+    fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError> {
+        Ok(TaskEvent {
+            time: row.u64()?,
+            missing_info: row.opt_enum()?,
+            job_id: row.u64()?,
+            task_index: row.u64()?,
+            machine_id: row.opt_u64()?,
+            event_type: row.enum_value()?,
+            user: row.opt_str()?.map(str::to_string),
+            scheduling_class: row.opt_enum()?,
+            priority: row.u8()?,
+            cpu_request: row.opt_f64()?,
+            memory_request: row.opt_f64()?,
+            disk_space_request: row.opt_f64()?,
+            different_machines_restrict: row.opt_u8()?,
+        })
+    }
+}
+
+impl CacheRecord for MachineEvent {
+    fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError> {
+        Ok(MachineEvent {
+            time: row.u64()?,
+            machine_id: row.u64()?,
+            event_type: row.enum_value()?,
+            platform_id: row.opt_str()?.map(str::to_string),
+            cpus: row.opt_f64()?,
+            memory: row.opt_f64()?,
+        })
+    }
+}
+
+impl CacheRecord for MachineAttribute {
+    fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError> {
+        Ok(MachineAttribute {
+            time: row.u64()?,
+            machine_id: row.u64()?,
+            attribute_name: row.str()?.to_string(),
+            attribute_value: row.opt_str()?.map(|v| AttributeValue::from(v.to_string())),
+            attribute_deleted: row.u8()? != 0,
+        })
+    }
+}
+
+impl CacheRecord for TaskConstraint {
+    fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError> {
+        Ok(TaskConstraint {
+            time: row.u64()?,
+            job_id: row.u64()?,
+            task_index: row.u64()?,
+            comparison_operator: row.enum_value()?,
+            attribute_name: row.str()?.to_string(),
+            attribute_value: row.opt_str()?.map(|v| AttributeValue::from(v.to_string())),
+        })
+    }
+}
+
+impl CacheRecord for TaskUsageRecord {
+    fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError> {
+        Ok(TaskUsageRecord {
+            start_time: row.u64()?,
+            end_time: row.u64()?,
+            job_id: row.u64()?,
+            task_index: row.u64()?,
+            machine_id: row.u64()?,
+            cpu_rate: row.opt_f64()?,
+            canonical_mem_usage: row.opt_f64()?,
+            assigned_mem_usage: row.opt_f64()?,
+            unmapped_page_cache: row.opt_f64()?,
+            total_page_cache: row.opt_f64()?,
+            max_mem_usage: row.opt_f64()?,
+            disk_io_time: row.opt_f64()?,
+            local_disk_space: row.opt_f64()?,
+            max_cpu_rate: row.opt_f64()?,
+            max_disk_io_tim: row.opt_f64()?,
+            cpi: row.opt_f64()?,
+            mapi: row.opt_f64()?,
+            sample_portion: row.opt_f64()?,
+            agg_type: row.opt_u8()?,
+            sampled_cpu_usage: row.opt_f64()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::AttributeValue;
+    use crate::iter::TraceFileIterator;
+    use crate::job_events::JobEvent;
+    use crate::machine_attributes::MachineAttribute;
+    use crate::machine_events::MachineEvent;
+    use crate::task_constraints::TaskConstraint;
+    use crate::task_events::TaskEvent;
+    use crate::task_usage::TaskUsageRecord;
+    use crate::testing::{scratch_dir, write_part};
+    use std::fmt::Debug;
+
+    fn parse<T: TraceRecord>(rows: &[&str]) -> Vec<T> {
+        let trace = scratch_dir();
+        write_part(&trace, T::TABLE, "part-00000-of-00001.csv", rows);
+        TraceFileIterator::new(&trace.join(T::TABLE))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    /// Writes `records` to a cache with blocks of `block_rows` rows, returning the cache
+    /// directory.
+    fn write<T: TraceRecord + Serialize>(records: &[T], block_rows: usize) -> String {
+        let dir = scratch_dir();
+        let dir = dir.to_str().unwrap();
+        let mut writer = CacheWriter::with_block_rows(dir, block_rows).unwrap();
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), records.len() as u64);
+        dir.to_string()
+    }
+
+    /// Parses `rows` as a table's only part file and checks that the records read back from a
+    /// cache of them, in full and partial blocks, equal the parsed ones.
+    fn round_trip<T: CacheRecord + Serialize + Debug>(rows: &[&str]) -> Vec<T> {
+        let records: Vec<T> = parse(rows);
+        assert_eq!(records.len(), rows.len());
+        let read: Vec<T> = CacheIterator::new(&write(&records, 2))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(format!("{:?}", read), format!("{:?}", records));
+        read
+    }
+
+    fn open_error<T: TraceRecord>(cache_dir: &str) -> String {
+        match CacheIterator::<T>::new(cache_dir) {
+            Err(Error::Cache { reason, .. }) => reason,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("opened a bad cache file"),
+        }
+    }
+
+    #[test]
+    fn machine_events_round_trip() {
+        round_trip::<MachineEvent>(&["0,1,0,plat1,0.5,0.25", "10,1,2,,,", "20,2,0,plat0,1,"]);
+    }
+
+    #[test]
+    fn machine_attributes_round_trip() {
+        let read = round_trip::<MachineAttribute>(&[
+            "0,1,rack,12,0",
+            "0,1,kernel,abcHASH1,0",
+            "0,1,ssd,,0",
+            "5,1,rack,,1",
+        ]);
+        assert_eq!(read[0].attribute_value, Some(AttributeValue::Integer(12)));
+        assert_eq!(
+            read[1].attribute_value,
+            Some(AttributeValue::Hash("abcHASH1".to_string()))
+        );
+        assert_eq!(read[2].attribute_value, None);
+        assert!(!read[2].attribute_deleted);
+        assert!(read[3].attribute_deleted);
+    }
+
+    #[test]
+    fn job_events_round_trip() {
+        round_trip::<JobEvent>(&[
+            "0,,1,0,user1,1,job1,ljob1",
+            "0,2,2,0,,,,",
+            "1000,,1,1,user1,9,job1,ljob1",
+        ]);
+    }
+
+    #[test]
+    fn task_events_round_trip() {
+        round_trip::<TaskEvent>(&[
+            "0,,1,0,,0,user1,1,9,0.0125,0.0159,0.0004,0",
+            "1000,1,1,0,7,1,,,9,,,,",
+            "2000,,1,1,,0,user1,3,0,0.5,,0.25,1",
+        ]);
+    }
+
+    #[test]
+    fn task_constraints_round_trip() {
+        round_trip::<TaskConstraint>(&["0,1,0,0,rack,1", "0,1,1,1,rack,", "0,1,2,3,kernel,h=="]);
+    }
+
+    #[test]
+    fn task_usage_round_trip() {
+        round_trip::<TaskUsageRecord>(&[
+            "600000000,900000000,1,0,2,0.05,0.03,0.04,0.001,0.002,0.035,0.001,0.0001,0.08,0.002,\
+             1.2,0.003,1.0,0,0.05",
+            "600000000,900000000,1,1,3,,,,,,,,,,,,,,,",
+            "900000000,1200000000,1,0,2,-0.0,0,1e-300,,,,,,,,,,0.5,1,",
+        ]);
+    }
+
+    #[test]
+    fn floats_keep_their_bits() {
+        let read = round_trip::<MachineEvent>(&[
+            "0,1,0,,0.5,0.25",
+            "1,1,2,,0.30000000000000004,123456.789",
+            "2,1,2,,-0.0,1e-300",
+            "3,1,2,,NaN,inf",
+        ]);
+        assert_eq!(read[1].cpus, Some(0.1 + 0.2));
+        assert_eq!(read[2].cpus.map(f64::to_bits), Some((-0.0f64).to_bits()));
+        assert!(read[3].cpus.unwrap().is_nan());
+    }
+
+    #[test]
+    fn floats_scale_to_integers_where_exact() {
+        assert_eq!(scale_float(0.25, 2), Some(25));
+        assert_eq!(scale_float(-0.0125, 4), Some(-125));
+        assert_eq!(scale_float(0.0125, 3), None);
+        assert_eq!(scale_float(0.1 + 0.2, 15), None);
+        assert_eq!(scale_float(1e300, 0), None);
+        // -0.0 would come back as 0.0
+        assert_eq!(scale_float(-0.0, 0), None);
+        assert_eq!(scale_float(f64::NAN, 0), None);
+    }
+
+    #[test]
+    fn packed_values_round_trip() {
+        let cases: [&[i64]; 6] = [
+            &[],
+            &[7, 7, 7],
+            &[-3, 7, 0],
+            &[0, 1 << 55, 12],
+            &[0, 1 << 56],
+            &[i64::MIN, i64::MAX, -1],
+        ];
+        for values in cases.iter() {
+            let mut buf = vec![0xaa];
+            put_packed(&mut buf, values);
+            let mut pos = 1;
+            let packed = Packed::read(&buf, &mut pos, values.len()).unwrap();
+            assert_eq!(pos, buf.len());
+            let read: Vec<i64> = (0..values.len())
+                .map(|i| packed.get(&buf, i) as i64)
+                .collect();
+            assert_eq!(read, *values);
+        }
+    }
+
+    /// A record with the column types that the trace's tables do not use.
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Flags {
+        time: u64,
+        flag: Option<bool>,
+        offset: i64,
+    }
+
+    impl TraceRecord for Flags {
+        const TABLE: &'static str = "flags";
+
+        fn time(&self) -> u64 {
+            self.time
+        }
+    }
+
+    impl CacheRecord for Flags {
+        fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError> {
+            Ok(Flags {
+                time: row.u64()?,
+                flag: row.opt_bool()?,
+                offset: row.i64()?,
+            })
+        }
+    }
+
+    /// Reads `flags` records, but takes their offset for a float.
+    #[derive(Debug, Deserialize, Serialize)]
+    struct FloatFlags {
+        time: u64,
+        flag: Option<bool>,
+        offset: i64,
+    }
+
+    impl TraceRecord for FloatFlags {
+        const TABLE: &'static str = "flags";
+
+        fn time(&self) -> u64 {
+            self.time
+        }
+    }
+
+    impl CacheRecord for FloatFlags {
+        fn from_cache_row(row: &mut CacheRow) -> Result<Self, RowError> {
+            Ok(FloatFlags {
+                time: row.u64()?,
+                flag: row.opt_bool()?,
+                offset: row.f64()? as i64,
+            })
+        }
+    }
+
+    #[test]
+    fn bools_and_signed_integers_round_trip() {
+        let flags: Vec<Flags> = [(None, -5), (Some(true), i64::MAX), (Some(false), i64::MIN)]
+            .iter()
+            .enumerate()
+            .map(|(t, (flag, offset))| Flags {
+                time: t as u64,
+                flag: *flag,
+                offset: *offset,
+            })
+            .collect();
+        let cache = write(&flags, 2);
+        let read: Vec<Flags> = CacheIterator::new(&cache)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(format!("{:?}", read), format!("{:?}", flags));
+
+        let mut read = CacheIterator::<FloatFlags>::new(&cache).unwrap();
+        match read.next() {
+            Some(Err(Error::Cache { reason, .. })) => assert!(
+                reason.ends_with("a field does not match its column"),
+                "{}",
+                reason
+            ),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert!(read.next().is_none());
+    }
+
+    #[test]
+    fn corrupt_blocks_are_rejected() {
+        let rows: Vec<String> = (0..4)
+            .map(|t| format!("{},1,2,plat{},0.5,", t, t))
+            .collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let cache = write(&parse::<MachineEvent>(&rows), 4);
+        let path = cache_path::<MachineEvent>(&cache);
+        let offset = CacheIterator::<MachineEvent>::new(&cache).unwrap().blocks[0].offset;
+        // lengthen the first column, which starts with its length
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[offset as usize] += 1;
+        fs::write(&path, &bytes).unwrap();
+
+        let mut read = CacheIterator::<MachineEvent>::new(&cache).unwrap();
+        match read.next() {
+            Some(Err(Error::Cache { reason, .. })) => {
+                assert_eq!(reason, format!("corrupt block at {}", offset))
+            }
+            r => panic!("unexpected result: {:?}", r.map(|r| r.map(|e| e.time))),
+        }
+    }
+
+    #[test]
+    fn range_skips_blocks() {
+        let rows: Vec<String> = (0..10).map(|t| format!("{},1,2,,,", t)).collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let cache = write(&parse::<MachineEvent>(&rows), 2);
+
+        // the blocks hold times 0-1, 2-3, ..., 8-9
+        let records = CacheIterator::<MachineEvent>::range(&cache, 3, 7).unwrap();
+        assert_eq!(records.blocks.len(), 3);
+        let times: Vec<u64> = records.map(|r| r.unwrap().time).collect();
+        assert_eq!(times, vec![3, 4, 5, 6]);
+
+        let records = CacheIterator::<MachineEvent>::range(&cache, 4, 6).unwrap();
+        assert_eq!(records.blocks.len(), 1);
+        let records = CacheIterator::<MachineEvent>::range(&cache, 10, u64::MAX).unwrap();
+        assert_eq!(records.blocks.len(), 0);
+        assert_eq!(records.count(), 0);
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let cache = write(&parse::<MachineEvent>(&["0,1,0,plat1,0.5,0.25"]), 2);
+        let path = cache_path::<MachineEvent>(&cache);
+        let bytes = fs::read(&path).unwrap();
+        // within the magic, within the header, and without the footer's magic
+        for len in [3, 10, bytes.len() - 2] {
+            fs::write(&path, &bytes[..len]).unwrap();
+            open_error::<MachineEvent>(&cache);
+        }
+    }
+
+    #[test]
+    fn other_tables_are_rejected() {
+        let cache = write(&parse::<MachineEvent>(&["0,1,0,plat1,0.5,0.25"]), 2);
+        fs::rename(
+            cache_path::<MachineEvent>(&cache),
+            cache_path::<JobEvent>(&cache),
+        )
+        .unwrap();
+        assert_eq!(
+            open_error::<JobEvent>(&cache),
+            "cache holds table machine_events, not job_events"
+        );
+    }
+
+    /// A record struct that no longer matches the one a cache was written with.
+    #[derive(Debug, Deserialize, Serialize)]
+    struct OldMachineEvent {
+        time: u64,
+        machine_id: u64,
+        cpus: Option<f64>,
+    }
+
+    impl TraceRecord for OldMachineEvent {
+        const TABLE: &'static str = "machine_events";
+
+        fn time(&self) -> u64 {
+            self.time
+        }
+    }
+
+    /// A record struct whose time is not its first field.
+    #[derive(Debug, Deserialize, Serialize)]
+    struct LateTime {
+        name: String,
+        time: u64,
+    }
+
+    impl TraceRecord for LateTime {
+        const TABLE: &'static str = "late_time";
+
+        fn time(&self) -> u64 {
+            self.time
+        }
+    }
+
+    #[test]
+    fn mismatched_columns_are_rejected() {
+        let old = OldMachineEvent {
+            time: 0,
+            machine_id: 1,
+            cpus: None,
+        };
+        let cache = write(&[old], 2);
+        assert_eq!(
+            open_error::<MachineEvent>(&cache),
+            "cached columns do not match the machine_events record struct"
+        );
+
+        let late = LateTime {
+            name: "a".to_string(),
+            time: 0,
+        };
+        let cache = write(&[late], 2);
+        assert_eq!(
+            open_error::<LateTime>(&cache),
+            "the first field of a late_time record is not its time"
+        );
+    }
+}
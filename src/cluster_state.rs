@@ -0,0 +1,485 @@
+use crate::common::{AttributeValue, SchedulingClass};
+use crate::error::Error;
+use crate::job_events::{JobEvent, JobEventType};
+use crate::machine_attributes::MachineAttribute;
+use crate::machine_events::{MachineEvent, MachineEventType};
+use crate::merge::{MergedEventIterator, Table, TraceEvent};
+use crate::task_events::{TaskEvent, TaskEventType};
+use std::collections::HashMap;
+use std::iter::Peekable;
+
+/// The tables replayed to reconstruct the cluster state.
+const STATE_TABLES: [Table; 4] = [
+    Table::MachineEvents,
+    Table::MachineAttributes,
+    Table::JobEvents,
+    Table::TaskEvents,
+];
+
+/// A machine that is part of the cluster.
+#[derive(Clone, Debug)]
+pub struct MachineState {
+    pub platform_id: Option<String>,
+    pub cpus: Option<f64>,
+    pub memory: Option<f64>,
+}
+
+/// A job that has been submitted and has not yet terminated.
+#[derive(Clone, Debug)]
+pub struct JobState {
+    pub submit_time: u64,
+    /// The time at which the first of the job's tasks was scheduled, if any has been.
+    pub schedule_time: Option<u64>,
+    pub user: Option<String>,
+    pub scheduling_class: Option<SchedulingClass>,
+    pub job_name: Option<String>,
+    pub logical_job_name: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// The task is waiting to be scheduled.
+    Pending,
+    /// The task has been placed on a machine.
+    Running { machine_id: u64 },
+}
+
+/// A task that has been submitted and has not yet terminated.
+#[derive(Clone, Debug)]
+pub struct TaskState {
+    pub status: TaskStatus,
+    pub submit_time: u64,
+    pub user: Option<String>,
+    pub scheduling_class: Option<SchedulingClass>,
+    pub priority: u8,
+    pub cpu_request: Option<f64>,
+    pub memory_request: Option<f64>,
+    pub disk_space_request: Option<f64>,
+}
+
+/// The state of the cluster (live machines, active jobs, and pending or running tasks) at a point
+/// in time, reconstructed by replaying the trace's events.
+///
+/// Machine attributes are kept for every machine ever seen, since the attribute table does not
+/// follow machines being removed and re-added.
+#[derive(Clone, Debug, Default)]
+pub struct ClusterState {
+    time: u64,
+    pub machines: HashMap<u64, MachineState>,
+    pub attributes: HashMap<u64, HashMap<String, Option<AttributeValue>>>,
+    pub jobs: HashMap<u64, JobState>,
+    pub tasks: HashMap<(u64, u64), TaskState>,
+}
+
+impl ClusterState {
+    /// Creates an empty cluster state, as at the beginning of the trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reconstructs the cluster state at `time`, i.e., after all events up to and including
+    /// `time` have been applied.
+    pub fn at(trace_path: &str, time: u64) -> Result<Self, Error> {
+        let mut state = ClusterState::new();
+        let events =
+            MergedEventIterator::range(trace_path, &STATE_TABLES, 0, time.saturating_add(1))?;
+        for ev in events {
+            state.apply(&ev?);
+        }
+        state.time = time;
+        Ok(state)
+    }
+
+    /// The timestamp of the last event applied (or the time requested from `at`).
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// The tasks waiting to be scheduled.
+    pub fn pending_tasks(&self) -> impl Iterator<Item = (&(u64, u64), &TaskState)> {
+        self.tasks
+            .iter()
+            .filter(|(_, t)| t.status == TaskStatus::Pending)
+    }
+
+    /// The tasks running on `machine_id`.
+    pub fn tasks_on(&self, machine_id: u64) -> impl Iterator<Item = (&(u64, u64), &TaskState)> {
+        self.tasks
+            .iter()
+            .filter(move |(_, t)| t.status == TaskStatus::Running { machine_id })
+    }
+
+    /// Applies an event to the state. Task usage records do not affect the cluster state and are
+    /// ignored.
+    pub fn apply(&mut self, event: &TraceEvent) {
+        self.time = self.time.max(event.time());
+        match event {
+            TraceEvent::Machine(e) => self.apply_machine_event(e),
+            TraceEvent::MachineAttribute(a) => self.apply_machine_attribute(a),
+            TraceEvent::Job(e) => self.apply_job_event(e),
+            TraceEvent::Task(e) => self.apply_task_event(e),
+            TraceEvent::TaskUsage(_) => (),
+        }
+    }
+
+    fn apply_machine_event(&mut self, e: &MachineEvent) {
+        match e.event_type {
+            MachineEventType::Add => {
+                self.machines.insert(
+                    e.machine_id,
+                    MachineState {
+                        platform_id: e.platform_id.clone(),
+                        cpus: e.cpus,
+                        memory: e.memory,
+                    },
+                );
+            }
+            MachineEventType::Remove => {
+                self.machines.remove(&e.machine_id);
+            }
+            MachineEventType::Update => {
+                let m = self
+                    .machines
+                    .entry(e.machine_id)
+                    .or_insert_with(|| MachineState {
+                        platform_id: None,
+                        cpus: None,
+                        memory: None,
+                    });
+                if e.platform_id.is_some() {
+                    m.platform_id = e.platform_id.clone();
+                }
+                m.cpus = e.cpus.or(m.cpus);
+                m.memory = e.memory.or(m.memory);
+            }
+        }
+    }
+
+    fn apply_machine_attribute(&mut self, a: &MachineAttribute) {
+        let attrs = self.attributes.entry(a.machine_id).or_default();
+        if a.attribute_deleted {
+            attrs.remove(&a.attribute_name);
+        } else {
+            attrs.insert(a.attribute_name.clone(), a.attribute_value.clone());
+        }
+    }
+
+    fn apply_job_event(&mut self, e: &JobEvent) {
+        match e.event_type {
+            JobEventType::Submit => {
+                self.jobs.insert(
+                    e.job_id,
+                    JobState {
+                        submit_time: e.time,
+                        schedule_time: None,
+                        user: e.user.clone(),
+                        scheduling_class: e.scheduling_class,
+                        job_name: e.job_name.clone(),
+                        logical_job_name: e.logical_job_name.clone(),
+                    },
+                );
+            }
+            JobEventType::Evict
+            | JobEventType::Fail
+            | JobEventType::Finish
+            | JobEventType::Kill
+            | JobEventType::Lost => {
+                self.jobs.remove(&e.job_id);
+            }
+            JobEventType::Schedule | JobEventType::UpdatePending | JobEventType::UpdateRunning => {
+                // jobs whose creation predates the trace only appear from their first later event
+                let j = self.jobs.entry(e.job_id).or_insert_with(|| JobState {
+                    submit_time: e.time,
+                    schedule_time: None,
+                    user: None,
+                    scheduling_class: None,
+                    job_name: None,
+                    logical_job_name: None,
+                });
+                if e.event_type == JobEventType::Schedule && j.schedule_time.is_none() {
+                    j.schedule_time = Some(e.time);
+                }
+                if e.user.is_some() {
+                    j.user = e.user.clone();
+                }
+                if e.job_name.is_some() {
+                    j.job_name = e.job_name.clone();
+                }
+                if e.logical_job_name.is_some() {
+                    j.logical_job_name = e.logical_job_name.clone();
+                }
+                j.scheduling_class = e.scheduling_class.or(j.scheduling_class);
+            }
+        }
+    }
+
+    fn apply_task_event(&mut self, e: &TaskEvent) {
+        let key = (e.job_id, e.task_index);
+        match e.event_type {
+            TaskEventType::Evict
+            | TaskEventType::Fail
+            | TaskEventType::Finish
+            | TaskEventType::Kill
+            | TaskEventType::Lost => {
+                self.tasks.remove(&key);
+            }
+            TaskEventType::Submit
+            | TaskEventType::Schedule
+            | TaskEventType::UpdatePending
+            | TaskEventType::UpdateRunning => {
+                let t = self.tasks.entry(key).or_insert_with(|| TaskState {
+                    status: TaskStatus::Pending,
+                    submit_time: e.time,
+                    user: None,
+                    scheduling_class: None,
+                    priority: e.priority,
+                    cpu_request: None,
+                    memory_request: None,
+                    disk_space_request: None,
+                });
+                match e.event_type {
+                    TaskEventType::Submit => {
+                        t.status = TaskStatus::Pending;
+                        t.submit_time = e.time;
+                    }
+                    TaskEventType::Schedule => {
+                        if let Some(machine_id) = e.machine_id {
+                            t.status = TaskStatus::Running { machine_id };
+                        }
+                    }
+                    _ => (),
+                }
+                if e.user.is_some() {
+                    t.user = e.user.clone();
+                }
+                t.scheduling_class = e.scheduling_class.or(t.scheduling_class);
+                t.priority = e.priority;
+                t.cpu_request = e.cpu_request.or(t.cpu_request);
+                t.memory_request = e.memory_request.or(t.memory_request);
+                t.disk_space_request = e.disk_space_request.or(t.disk_space_request);
+            }
+        }
+    }
+}
+
+/// Incrementally advances a `ClusterState` through the trace.
+pub struct ClusterReplay {
+    state: ClusterState,
+    events: Peekable<MergedEventIterator>,
+}
+
+impl ClusterReplay {
+    /// Starts a replay from the beginning of the trace at `trace_path`.
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        Self::with_tables(trace_path, &STATE_TABLES)
+    }
+
+    /// Starts a replay of only some of the state tables (e.g., just machine and task events).
+    /// The parts of the state that the other tables fill in stay empty.
+    pub fn with_tables(trace_path: &str, tables: &[Table]) -> Result<Self, Error> {
+        Ok(ClusterReplay {
+            state: ClusterState::new(),
+            events: MergedEventIterator::new(trace_path, tables)?.peekable(),
+        })
+    }
+
+    /// Applies all events up to and including `time`, and returns the resulting state. On error,
+    /// the events before the erroneous record have been applied, and advancing again continues
+    /// after it.
+    pub fn advance_to(&mut self, time: u64) -> Result<&ClusterState, Error> {
+        while let Some(r) = self.events.peek() {
+            if let Ok(ev) = r {
+                if ev.time() > time {
+                    break;
+                }
+            }
+            match self.events.next() {
+                Some(Ok(ev)) => self.state.apply(&ev),
+                Some(Err(e)) => return Err(e),
+                None => unreachable!(),
+            }
+        }
+        self.state.time = self.state.time.max(time);
+        Ok(&self.state)
+    }
+
+    /// The state reached so far.
+    pub fn state(&self) -> &ClusterState {
+        &self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{parse, scratch_dir, write_part};
+
+    fn machine_event(line: &str) -> TraceEvent {
+        TraceEvent::Machine(parse(line))
+    }
+
+    fn job_event(line: &str) -> TraceEvent {
+        TraceEvent::Job(parse(line))
+    }
+
+    fn task_event(line: &str) -> TraceEvent {
+        TraceEvent::Task(parse(line))
+    }
+
+    fn apply(state: &mut ClusterState, events: &[TraceEvent]) {
+        for e in events {
+            state.apply(e);
+        }
+    }
+
+    /// A trace with one part per state table: machine 1 is added at time 0 and updated at 200, and
+    /// a task is submitted at 100, scheduled at 200 and finishes at 300, after an invalid record if
+    /// `bad_record` is set.
+    fn fixture(bad_record: bool) -> String {
+        let trace = scratch_dir();
+        let part = "part-00000-of-00001.csv";
+        write_part(
+            &trace,
+            "machine_events",
+            part,
+            &["0,1,0,p1,0.5,0.5", "200,1,2,,0.75,"],
+        );
+        write_part(&trace, "machine_attributes", part, &["0,1,kernel,5,0"]);
+        write_part(&trace, "job_events", part, &["100,,1,0,u1,2,j1,l1"]);
+        let mut rows = vec![
+            "100,,1,0,,0,u1,2,9,0.25,0.125,,0",
+            "200,,1,0,1,1,u1,2,9,,,,0",
+            "300,,1,0,1,4,u1,2,9,,,,0",
+        ];
+        if bad_record {
+            // no event type 99
+            rows.insert(2, "250,,1,0,1,99,u1,2,9,,,,0");
+        }
+        write_part(&trace, "task_events", part, &rows);
+        trace.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn machines_can_be_removed_and_re_added() {
+        let mut state = ClusterState::new();
+        apply(
+            &mut state,
+            &[
+                machine_event("0,1,0,p1,0.5,0.5"),
+                machine_event("0,2,0,p1,0.5,0.5"),
+                machine_event("10,1,1,,,"),
+            ],
+        );
+        assert!(!state.machines.contains_key(&1));
+        assert!(state.machines.contains_key(&2));
+
+        state.apply(&machine_event("20,1,0,p2,1,"));
+        let m = &state.machines[&1];
+        assert_eq!(m.platform_id.as_deref(), Some("p2"));
+        assert_eq!((m.cpus, m.memory), (Some(1.0), None));
+        assert_eq!(state.time(), 20);
+    }
+
+    #[test]
+    fn updates_keep_missing_fields() {
+        let mut state = ClusterState::new();
+        apply(
+            &mut state,
+            &[
+                machine_event("0,1,0,p1,0.5,0.5"),
+                machine_event("10,1,2,,,0.25"),
+                job_event("0,,1,0,u1,2,j1,l1"),
+                job_event("10,,1,8,,,,"),
+                task_event("0,,1,0,,0,u1,2,9,0.25,0.125,0.01,0"),
+                task_event("10,,1,0,,7,,,10,,0.5,,0"),
+            ],
+        );
+        let m = &state.machines[&1];
+        assert_eq!(m.platform_id.as_deref(), Some("p1"));
+        assert_eq!((m.cpus, m.memory), (Some(0.5), Some(0.25)));
+        let j = &state.jobs[&1];
+        assert_eq!(j.user.as_deref(), Some("u1"));
+        assert_eq!(j.job_name.as_deref(), Some("j1"));
+        assert_eq!(j.scheduling_class, Some(SchedulingClass::Class2));
+        let t = &state.tasks[&(1, 0)];
+        assert_eq!(t.user.as_deref(), Some("u1"));
+        assert_eq!(t.priority, 10);
+        assert_eq!(
+            (t.cpu_request, t.memory_request, t.disk_space_request),
+            (Some(0.25), Some(0.5), Some(0.01))
+        );
+    }
+
+    #[test]
+    fn evicted_tasks_are_placed_again() {
+        let mut state = ClusterState::new();
+        apply(
+            &mut state,
+            &[
+                task_event("10,,1,0,,0,u1,2,9,0.25,,,0"),
+                task_event("20,,1,0,7,1,u1,2,9,,,,0"),
+            ],
+        );
+        assert_eq!(
+            state.tasks[&(1, 0)].status,
+            TaskStatus::Running { machine_id: 7 }
+        );
+        assert_eq!(state.tasks_on(7).count(), 1);
+        assert_eq!(state.pending_tasks().count(), 0);
+
+        state.apply(&task_event("30,,1,0,7,2,u1,2,9,,,,0"));
+        assert!(state.tasks.is_empty());
+
+        state.apply(&task_event("40,,1,0,,0,u1,2,9,0.5,,,0"));
+        let t = &state.tasks[&(1, 0)];
+        assert_eq!((t.status, t.submit_time), (TaskStatus::Pending, 40));
+        assert_eq!(t.cpu_request, Some(0.5));
+        assert_eq!(state.pending_tasks().count(), 1);
+        assert_eq!(state.tasks_on(7).count(), 0);
+
+        state.apply(&task_event("50,,1,0,8,1,u1,2,9,,,,0"));
+        assert_eq!(state.tasks_on(8).count(), 1);
+        assert_eq!(state.tasks[&(1, 0)].submit_time, 40);
+    }
+
+    #[test]
+    fn at_includes_events_at_the_time() {
+        let trace = fixture(false);
+        let state = ClusterState::at(&trace, 99).unwrap();
+        assert_eq!(state.time(), 99);
+        assert_eq!(state.machines[&1].cpus, Some(0.5));
+        assert_eq!(
+            state.attributes[&1]["kernel"],
+            Some(AttributeValue::Integer(5))
+        );
+        assert!(state.jobs.is_empty() && state.tasks.is_empty());
+
+        let state = ClusterState::at(&trace, 100).unwrap();
+        assert_eq!(state.jobs[&1].submit_time, 100);
+        assert_eq!(state.tasks[&(1, 0)].status, TaskStatus::Pending);
+
+        let state = ClusterState::at(&trace, 200).unwrap();
+        assert_eq!(state.machines[&1].cpus, Some(0.75));
+        assert_eq!(state.tasks_on(1).count(), 1);
+
+        let state = ClusterState::at(&trace, 300).unwrap();
+        assert!(state.tasks.is_empty());
+    }
+
+    #[test]
+    fn advance_to_resumes_after_an_error() {
+        let trace = fixture(true);
+        let mut replay = ClusterReplay::new(&trace).unwrap();
+        assert_eq!(replay.advance_to(100).unwrap().tasks.len(), 1);
+        // the invalid record's time is unknown, so it is reported as soon as it is reached
+        match replay.advance_to(200) {
+            Err(Error::Schema { line, .. }) => assert_eq!(line, Some(3)),
+            other => panic!("expected a schema error, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(replay.state().tasks_on(1).count(), 1);
+        assert_eq!(replay.advance_to(200).unwrap().tasks_on(1).count(), 1);
+        let state = replay.advance_to(400).unwrap();
+        assert!(state.tasks.is_empty());
+        assert_eq!(state.time(), 400);
+    }
+}
@@ -1,6 +1,22 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serializer};
+use std::fmt;
+
 pub static TRACE_START_TIME: u64 = 600_000_000;
 
-#[derive(Debug, Deserialize_repr)]
+/// The timestamp of events that occurred after the end of the trace window (2^63 - 1).
+pub static AFTER_TRACE_TIME: u64 = i64::MAX as u64;
+
+/// A record in one of the trace's tables.
+pub trait TraceRecord: DeserializeOwned {
+    /// The name of the table's directory within the trace (e.g., `task_usage`).
+    const TABLE: &'static str;
+
+    /// The timestamp (in microseconds) by which the table's records are ordered.
+    fn time(&self) -> u64;
+}
+
+#[derive(Clone, Copy, Debug, Deserialize_repr, Serialize_repr, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum MissingInfo {
     /// (0): "we did not find a record representing the given event, but a later snapshot of the job
@@ -18,13 +34,13 @@ pub enum MissingInfo {
     ExistsButNoCreation = 2,
 }
 
-impl Into<MissingInfo> for &str {
-    fn into(self) -> MissingInfo {
-        if self == "0" {
+impl From<&str> for MissingInfo {
+    fn from(s: &str) -> MissingInfo {
+        if s == "0" {
             MissingInfo::SnapshotButNoTransition
-        } else if self == "1" {
+        } else if s == "1" {
             MissingInfo::NoSnapshotOrTransition
-        } else if self == "2" {
+        } else if s == "2" {
             MissingInfo::ExistsButNoCreation
         } else {
             unreachable!()
@@ -32,7 +48,9 @@ impl Into<MissingInfo> for &str {
     }
 }
 
-#[derive(Debug, Deserialize_repr)]
+#[derive(
+    Clone, Copy, Debug, Deserialize_repr, Serialize_repr, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
 #[repr(u8)]
 pub enum SchedulingClass {
     /// "a non-production task (e.g., development, non-business-critical analyses, etc.)"
@@ -44,18 +62,68 @@ pub enum SchedulingClass {
     Unknown = 9,
 }
 
-impl Into<SchedulingClass> for &str {
-    fn into(self) -> SchedulingClass {
-        if self == "0" {
+impl From<&str> for SchedulingClass {
+    fn from(s: &str) -> SchedulingClass {
+        if s == "0" {
             SchedulingClass::Class0
-        } else if self == "1" {
+        } else if s == "1" {
             SchedulingClass::Class1
-        } else if self == "2" {
+        } else if s == "2" {
             SchedulingClass::Class2
-        } else if self == "3" {
+        } else if s == "3" {
             SchedulingClass::Class3
         } else {
             unreachable!()
         }
     }
 }
+
+impl fmt::Display for SchedulingClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", *self as u8)
+    }
+}
+
+/// A `STRING_HASH_OR_INTEGER` value, as found in machine attributes and task constraints.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum AttributeValue {
+    /// A plain integer value (e.g., a kernel version number).
+    Integer(i64),
+    /// An obfuscated string value.
+    Hash(String),
+}
+
+impl From<String> for AttributeValue {
+    fn from(s: String) -> AttributeValue {
+        match s.parse() {
+            Ok(i) => AttributeValue::Integer(i),
+            Err(_) => AttributeValue::Hash(s),
+        }
+    }
+}
+
+impl From<AttributeValue> for String {
+    fn from(v: AttributeValue) -> String {
+        match v {
+            AttributeValue::Integer(i) => i.to_string(),
+            AttributeValue::Hash(s) => s,
+        }
+    }
+}
+
+/// Deserializes a `BOOLEAN` field, which the trace encodes as 0 or 1.
+pub(crate) fn bool_from_int<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(u8::deserialize(deserializer)? != 0)
+}
+
+/// Serializes a `BOOLEAN` field as the trace encodes it.
+pub(crate) fn bool_to_int<S>(value: &bool, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u8(*value as u8)
+}
@@ -0,0 +1,274 @@
+use crate::attribute_store::AttributeStore;
+use crate::error::Error;
+use crate::machine_timeline::MachineTimeline;
+use crate::task_constraints::{TaskConstraint, TaskConstraintIterator};
+use crate::task_events::{TaskEvent, TaskEventType};
+use std::collections::HashMap;
+
+/// The constraints recorded for a task at a timestamp.
+type ConstraintSet = (u64, Vec<TaskConstraint>);
+
+/// The placement constraints of every task over time, built from the task constraints table.
+///
+/// The constraints recorded for a task at the same timestamp form its constraint set from then
+/// on, replacing any set recorded earlier.
+#[derive(Clone, Debug, Default)]
+pub struct ConstraintStore {
+    tasks: HashMap<(u64, u64), Vec<ConstraintSet>>,
+}
+
+impl ConstraintStore {
+    /// Builds the store from the task constraints of the trace at `trace_path`.
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        Self::from_constraints(TaskConstraintIterator::new(trace_path)?)
+    }
+
+    /// Builds the store from task constraint records in time order.
+    pub fn from_constraints<I>(constraints: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Result<TaskConstraint, Error>>,
+    {
+        let mut store = ConstraintStore::default();
+        for c in constraints {
+            let c = c?;
+            let sets = store.tasks.entry((c.job_id, c.task_index)).or_default();
+            match sets.last_mut() {
+                Some((t, set)) if *t == c.time => set.push(c),
+                _ => sets.push((c.time, vec![c])),
+            }
+        }
+        Ok(store)
+    }
+
+    /// The constraints in effect for a task at `time`.
+    pub fn constraints_at(&self, job_id: u64, task_index: u64, time: u64) -> &[TaskConstraint] {
+        let sets = match self.tasks.get(&(job_id, task_index)) {
+            Some(sets) => sets,
+            None => return &[],
+        };
+        let i = sets.partition_point(|(t, _)| *t <= time);
+        sets[..i].last().map_or(&[], |(_, set)| set.as_slice())
+    }
+}
+
+/// A task placement recorded in the trace that violates the task's constraints.
+#[derive(Clone, Debug)]
+pub struct ConstraintViolation {
+    /// The SCHEDULE event that placed the task.
+    pub event: TaskEvent,
+    /// The constraints the machine did not satisfy.
+    pub violated: Vec<TaskConstraint>,
+}
+
+/// Evaluates tasks' placement constraints against machines' attributes at any point in time.
+pub struct ConstraintChecker {
+    constraints: ConstraintStore,
+    attributes: AttributeStore,
+    machines: MachineTimeline,
+}
+
+impl ConstraintChecker {
+    /// Loads the task constraints, machine attributes and machine events of the trace at
+    /// `trace_path`.
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        Ok(Self::from_parts(
+            ConstraintStore::new(trace_path)?,
+            AttributeStore::new(trace_path)?,
+            MachineTimeline::new(trace_path)?,
+        ))
+    }
+
+    pub fn from_parts(
+        constraints: ConstraintStore,
+        attributes: AttributeStore,
+        machines: MachineTimeline,
+    ) -> Self {
+        ConstraintChecker {
+            constraints,
+            attributes,
+            machines,
+        }
+    }
+
+    pub fn constraints(&self) -> &ConstraintStore {
+        &self.constraints
+    }
+
+    /// Whether `machine_id` satisfies a constraint at `time`.
+    pub fn satisfies(&self, constraint: &TaskConstraint, machine_id: u64, time: u64) -> bool {
+        let value = self
+            .attributes
+            .attribute_at(machine_id, &constraint.attribute_name, time)
+            .flatten();
+        constraint.is_satisfied_by(value)
+    }
+
+    /// The constraints of a task that `machine_id` does not satisfy at `time`.
+    pub fn unsatisfied(
+        &self,
+        job_id: u64,
+        task_index: u64,
+        machine_id: u64,
+        time: u64,
+    ) -> Vec<&TaskConstraint> {
+        self.constraints
+            .constraints_at(job_id, task_index, time)
+            .iter()
+            .filter(|c| !self.satisfies(c, machine_id, time))
+            .collect()
+    }
+
+    /// The machines in the cluster at `time` that satisfy all of a task's constraints, in
+    /// ascending ID order.
+    pub fn satisfying_machines(&self, job_id: u64, task_index: u64, time: u64) -> Vec<u64> {
+        let constraints = self.constraints.constraints_at(job_id, task_index, time);
+        let mut machines: Vec<u64> = self
+            .machines
+            .machine_ids()
+            .filter(|m| self.machines.capacity_at(*m, time).is_some())
+            .filter(|m| constraints.iter().all(|c| self.satisfies(c, *m, time)))
+            .collect();
+        machines.sort_unstable();
+        machines
+    }
+
+    /// The fraction of the machines in the cluster at `time` that satisfy all of a task's
+    /// constraints, or `None` if there are no machines.
+    pub fn selectivity(&self, job_id: u64, task_index: u64, time: u64) -> Option<f64> {
+        let live = self
+            .machines
+            .machine_ids()
+            .filter(|m| self.machines.capacity_at(*m, time).is_some())
+            .count();
+        if live == 0 {
+            return None;
+        }
+        Some(self.satisfying_machines(job_id, task_index, time).len() as f64 / live as f64)
+    }
+
+    /// Checks the placement made by every SCHEDULE event against the task's constraints, and
+    /// returns those that violate them.
+    pub fn violations<I>(&self, events: I) -> Result<Vec<ConstraintViolation>, Error>
+    where
+        I: IntoIterator<Item = Result<TaskEvent, Error>>,
+    {
+        let mut violations = Vec::new();
+        for e in events {
+            let e = e?;
+            let machine_id = match (e.event_type, e.machine_id) {
+                (TaskEventType::Schedule, Some(m)) => m,
+                _ => continue,
+            };
+            let violated: Vec<TaskConstraint> = self
+                .unsatisfied(e.job_id, e.task_index, machine_id, e.time)
+                .into_iter()
+                .cloned()
+                .collect();
+            if !violated.is_empty() {
+                violations.push(ConstraintViolation { event: e, violated });
+            }
+        }
+        Ok(violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TraceRecord;
+    use crate::testing::parse;
+
+    /// Builds `R` from records parsed from CSV lines.
+    fn from_lines<T, R, F>(lines: &[&str], build: F) -> R
+    where
+        T: TraceRecord,
+        F: FnOnce(Vec<Result<T, Error>>) -> Result<R, Error>,
+    {
+        build(lines.iter().map(|l| Ok(parse(l))).collect()).unwrap()
+    }
+
+    /// Machines 1 to 3 join at time 5 and machine 3 leaves at 20; machine 4 has attributes but
+    /// is never part of the cluster. Task 1:0 must run in rack r1 until 30, and on a kernel
+    /// newer than 5 from then on.
+    fn checker() -> ConstraintChecker {
+        let constraints = from_lines(
+            &["0,1,0,0,rack,r1", "30,1,0,3,kernel,5"],
+            ConstraintStore::from_constraints,
+        );
+        let attributes = from_lines(
+            &[
+                "0,1,rack,r1,0",
+                "0,1,kernel,5,0",
+                "0,2,rack,r2,0",
+                "0,2,kernel,6,0",
+                "0,3,rack,r1,0",
+                "0,3,kernel,7,0",
+                "0,4,rack,r1,0",
+            ],
+            AttributeStore::from_attributes,
+        );
+        let machines = from_lines(
+            &[
+                "5,1,0,p1,0.5,0.5",
+                "5,2,0,p1,0.5,0.5",
+                "5,3,0,p1,0.5,0.5",
+                "20,3,1,,,",
+            ],
+            MachineTimeline::from_events,
+        );
+        ConstraintChecker::from_parts(constraints, attributes, machines)
+    }
+
+    #[test]
+    fn later_constraint_sets_replace_earlier_ones() {
+        let c = checker();
+        let names = |time| -> Vec<String> {
+            c.constraints()
+                .constraints_at(1, 0, time)
+                .iter()
+                .map(|c| c.attribute_name.clone())
+                .collect()
+        };
+        assert_eq!(names(0), ["rack"]);
+        assert_eq!(names(29), ["rack"]);
+        assert_eq!(names(30), ["kernel"]);
+        assert!(c.constraints().constraints_at(2, 0, 30).is_empty());
+    }
+
+    #[test]
+    fn machines_are_selected_by_constraints() {
+        let c = checker();
+        assert_eq!(c.satisfying_machines(1, 0, 10), vec![1, 3]);
+        assert_eq!(c.selectivity(1, 0, 10), Some(2.0 / 3.0));
+        assert_eq!(c.satisfying_machines(1, 0, 20), vec![1]);
+        assert_eq!(c.selectivity(1, 0, 20), Some(0.5));
+        assert_eq!(c.satisfying_machines(1, 0, 30), vec![2]);
+        // unconstrained tasks can run anywhere
+        assert_eq!(c.satisfying_machines(2, 0, 10), vec![1, 2, 3]);
+        assert_eq!(c.selectivity(2, 0, 10), Some(1.0));
+        assert_eq!(c.selectivity(1, 0, 0), None);
+    }
+
+    #[test]
+    fn violating_placements_are_reported() {
+        let c = checker();
+        let events: Vec<Result<TaskEvent, Error>> = [
+            "8,,1,0,,0,u1,1,9,,,,0",
+            "10,,1,0,2,1,u1,1,9,,,,0",
+            "12,,1,0,2,2,u1,1,9,,,,0",
+            "15,,1,0,1,1,u1,1,9,,,,0",
+            "30,,1,0,1,8,u1,1,9,,,,0",
+            "35,,1,0,1,1,u1,1,9,,,,0",
+        ]
+        .iter()
+        .map(|l| Ok(parse(l)))
+        .collect();
+        let violations = c.violations(events).unwrap();
+        let found: Vec<_> = violations
+            .iter()
+            .map(|v| (v.event.time, v.violated[0].attribute_name.as_str()))
+            .collect();
+        assert_eq!(found, vec![(10, "rack"), (35, "kernel")]);
+        assert!(violations.iter().all(|v| v.violated.len() == 1));
+    }
+}
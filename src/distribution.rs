@@ -0,0 +1,513 @@
+use hdrhistogram::Histogram;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::io;
+
+/// The quantiles reported when nothing more specific is needed.
+pub static COMMON_QUANTILES: [f64; 5] = [0.5, 0.9, 0.95, 0.99, 0.999];
+
+/// The distribution of a non-negative `f64` metric (e.g., a normalized resource usage), backed by
+/// an auto-resizing HDR histogram.
+///
+/// Values are recorded with `decimals` digits after the decimal point, and quantiles are accurate
+/// to `significant_figures` significant digits.
+#[derive(Clone, Debug)]
+pub struct Distribution {
+    hist: Histogram<u64>,
+    decimals: u32,
+    significant_figures: u8,
+}
+
+impl Default for Distribution {
+    /// Four decimals and two significant figures, which suits the trace's normalized values.
+    fn default() -> Self {
+        Self::new(4, 2)
+    }
+}
+
+impl Distribution {
+    pub fn new(decimals: u32, significant_figures: u8) -> Self {
+        assert!(
+            significant_figures <= 5,
+            "at most 5 significant figures are supported"
+        );
+        Distribution {
+            hist: Histogram::new(significant_figures).expect("valid histogram parameters"),
+            decimals,
+            significant_figures,
+        }
+    }
+
+    fn scale(&self) -> f64 {
+        10f64.powi(self.decimals as i32)
+    }
+
+    /// Records a value. Negative values are recorded as zero, and NaNs are ignored.
+    pub fn record(&mut self, value: f64) {
+        self.record_n(value, 1)
+    }
+
+    /// Records `count` occurrences of a value.
+    pub fn record_n(&mut self, value: f64, count: u64) {
+        if value.is_nan() {
+            return;
+        }
+        let scaled = (value.max(0.0) * self.scale()).round() as u64;
+        if self.hist.record_n(scaled, count).is_err() {
+            // beyond the largest value an auto-resizing histogram can track
+            self.hist.saturating_record_n(scaled, count);
+        }
+    }
+
+    /// Adds the values recorded in another distribution with the same precision (e.g., one
+    /// recorded on a different thread).
+    pub fn merge(&mut self, other: &Distribution) {
+        assert_eq!(
+            (self.decimals, self.significant_figures),
+            (other.decimals, other.significant_figures),
+            "cannot merge distributions with different precisions"
+        );
+        self.hist
+            .add(&other.hist)
+            .expect("auto-resizing histograms can always be added");
+    }
+
+    /// The number of values recorded.
+    pub fn len(&self) -> u64 {
+        self.hist.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hist.is_empty()
+    }
+
+    pub fn min(&self) -> f64 {
+        self.hist.min() as f64 / self.scale()
+    }
+
+    pub fn max(&self) -> f64 {
+        self.hist.max() as f64 / self.scale()
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.hist.mean() / self.scale()
+    }
+
+    /// The value at quantile `q`, in `[0, 1]`.
+    pub fn value_at_quantile(&self, q: f64) -> f64 {
+        self.hist.value_at_quantile(q) as f64 / self.scale()
+    }
+
+    /// The values at each of the quantiles in `qs`.
+    pub fn quantiles(&self, qs: &[f64]) -> Vec<f64> {
+        qs.iter().map(|q| self.value_at_quantile(*q)).collect()
+    }
+
+    /// The cumulative distribution function, as `(value, fraction of values at or below it)`
+    /// points, one per distinct recorded histogram bucket.
+    pub fn cdf(&self) -> Vec<(f64, f64)> {
+        let scale = self.scale();
+        self.hist
+            .iter_recorded()
+            .map(|v| (v.value_iterated_to() as f64 / scale, v.quantile()))
+            .collect()
+    }
+
+    /// Writes a quantile table with a single row to `w` as CSV, with a header row.
+    pub fn write_quantiles_csv<W: io::Write>(&self, w: W, qs: &[f64]) -> io::Result<()> {
+        write_quantiles_csv(w, qs, vec![(None, self)])
+    }
+
+    /// Writes a quantile table to `w` as a JSON object.
+    pub fn write_quantiles_json<W: io::Write>(&self, w: W, qs: &[f64]) -> io::Result<()> {
+        serde_json::to_writer_pretty(w, &QuantileRow::new(None, self, qs))?;
+        Ok(())
+    }
+
+    /// Writes the CDF to `w` as CSV, with a header row.
+    pub fn write_cdf_csv<W: io::Write>(&self, w: W) -> io::Result<()> {
+        write_cdf_csv(w, vec![(None, self)])
+    }
+
+    /// Writes the CDF to `w` as a JSON object.
+    pub fn write_cdf_json<W: io::Write>(&self, w: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(w, &CdfRow::new(None, self))?;
+        Ok(())
+    }
+}
+
+/// Separate distributions of a metric for each value of a grouping key (e.g., job ID, user,
+/// scheduling class or machine platform).
+#[derive(Clone, Debug)]
+pub struct GroupedDistribution<K> {
+    groups: HashMap<K, Distribution>,
+    decimals: u32,
+    significant_figures: u8,
+}
+
+impl<K: Eq + Hash> Default for GroupedDistribution<K> {
+    fn default() -> Self {
+        Self::new(4, 2)
+    }
+}
+
+impl<K: Eq + Hash> GroupedDistribution<K> {
+    /// Creates an empty grouped distribution whose groups record values at the given precision
+    /// (see `Distribution`).
+    pub fn new(decimals: u32, significant_figures: u8) -> Self {
+        GroupedDistribution {
+            groups: HashMap::new(),
+            decimals,
+            significant_figures,
+        }
+    }
+
+    /// Records a value in the distribution of `key`'s group.
+    pub fn record(&mut self, key: K, value: f64) {
+        let (decimals, significant_figures) = (self.decimals, self.significant_figures);
+        self.groups
+            .entry(key)
+            .or_insert_with(|| Distribution::new(decimals, significant_figures))
+            .record(value);
+    }
+
+    /// Adds the values recorded in another grouped distribution, group by group.
+    pub fn merge(&mut self, other: GroupedDistribution<K>) {
+        for (key, d) in other.groups {
+            match self.groups.get_mut(&key) {
+                Some(mine) => mine.merge(&d),
+                None => {
+                    self.groups.insert(key, d);
+                }
+            }
+        }
+    }
+
+    /// The distribution of a group, if any values were recorded for it.
+    pub fn get(&self, key: &K) -> Option<&Distribution> {
+        self.groups.get(key)
+    }
+
+    /// The number of groups.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &Distribution)> {
+        self.groups.iter()
+    }
+
+    /// The distribution of all values recorded, regardless of their group.
+    pub fn combined(&self) -> Distribution {
+        let mut all = Distribution::new(self.decimals, self.significant_figures);
+        for d in self.groups.values() {
+            all.merge(d);
+        }
+        all
+    }
+}
+
+impl<K: Eq + Hash + Ord + Display> GroupedDistribution<K> {
+    fn sorted(&self) -> Vec<(Option<String>, &Distribution)> {
+        let mut groups: Vec<(&K, &Distribution)> = self.groups.iter().collect();
+        groups.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        groups
+            .into_iter()
+            .map(|(k, d)| (Some(k.to_string()), d))
+            .collect()
+    }
+
+    /// Writes a quantile table with one row per group, in key order, to `w` as CSV.
+    pub fn write_quantiles_csv<W: io::Write>(&self, w: W, qs: &[f64]) -> io::Result<()> {
+        write_quantiles_csv(w, qs, self.sorted())
+    }
+
+    /// Writes a quantile table with one entry per group, in key order, to `w` as a JSON array.
+    pub fn write_quantiles_json<W: io::Write>(&self, w: W, qs: &[f64]) -> io::Result<()> {
+        let rows: Vec<QuantileRow> = self
+            .sorted()
+            .into_iter()
+            .map(|(k, d)| QuantileRow::new(k, d, qs))
+            .collect();
+        serde_json::to_writer_pretty(w, &rows)?;
+        Ok(())
+    }
+
+    /// Writes every group's CDF, in key order, to `w` as CSV.
+    pub fn write_cdf_csv<W: io::Write>(&self, w: W) -> io::Result<()> {
+        write_cdf_csv(w, self.sorted())
+    }
+
+    /// Writes every group's CDF, in key order, to `w` as a JSON array.
+    pub fn write_cdf_json<W: io::Write>(&self, w: W) -> io::Result<()> {
+        let rows: Vec<CdfRow> = self
+            .sorted()
+            .into_iter()
+            .map(|(k, d)| CdfRow::new(k, d))
+            .collect();
+        serde_json::to_writer_pretty(w, &rows)?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct QuantileRow {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    count: u64,
+    mean: f64,
+    quantiles: Vec<QuantileValue>,
+}
+
+#[derive(Serialize)]
+struct QuantileValue {
+    quantile: f64,
+    value: f64,
+}
+
+impl QuantileRow {
+    fn new(key: Option<String>, d: &Distribution, qs: &[f64]) -> Self {
+        QuantileRow {
+            key,
+            count: d.len(),
+            mean: d.mean(),
+            quantiles: qs
+                .iter()
+                .map(|q| QuantileValue {
+                    quantile: *q,
+                    value: d.value_at_quantile(*q),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CdfRow {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    count: u64,
+    cdf: Vec<CdfPoint>,
+}
+
+#[derive(Serialize)]
+struct CdfPoint {
+    value: f64,
+    fraction: f64,
+}
+
+impl CdfRow {
+    fn new(key: Option<String>, d: &Distribution) -> Self {
+        CdfRow {
+            key,
+            count: d.len(),
+            cdf: d
+                .cdf()
+                .into_iter()
+                .map(|(value, fraction)| CdfPoint { value, fraction })
+                .collect(),
+        }
+    }
+}
+
+fn write_quantiles_csv<W: io::Write>(
+    w: W,
+    qs: &[f64],
+    rows: Vec<(Option<String>, &Distribution)>,
+) -> io::Result<()> {
+    let keyed = rows.iter().any(|(k, _)| k.is_some());
+    let mut writer = csv::Writer::from_writer(w);
+    let mut header: Vec<String> = Vec::new();
+    if keyed {
+        header.push("key".to_string());
+    }
+    header.push("count".to_string());
+    header.push("mean".to_string());
+    header.extend(qs.iter().map(|q| q.to_string()));
+    writer.write_record(&header)?;
+    for (key, d) in rows {
+        let mut record: Vec<String> = key.into_iter().collect();
+        record.push(d.len().to_string());
+        record.push(d.mean().to_string());
+        record.extend(d.quantiles(qs).iter().map(|v| v.to_string()));
+        writer.write_record(&record)?;
+    }
+    writer.flush()
+}
+
+fn write_cdf_csv<W: io::Write>(w: W, rows: Vec<(Option<String>, &Distribution)>) -> io::Result<()> {
+    let keyed = rows.iter().any(|(k, _)| k.is_some());
+    let mut writer = csv::Writer::from_writer(w);
+    if keyed {
+        writer.write_record(["key", "value", "fraction"])?;
+    } else {
+        writer.write_record(["value", "fraction"])?;
+    }
+    for (key, d) in rows {
+        for (value, fraction) in d.cdf() {
+            let mut record: Vec<String> = key.iter().cloned().collect();
+            record.push(value.to_string());
+            record.push(fraction.to_string());
+            writer.write_record(&record)?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack::PriorityBand;
+    use serde_json::{json, Value};
+
+    fn distribution(decimals: u32, values: &[f64]) -> Distribution {
+        let mut d = Distribution::new(decimals, 3);
+        for v in values {
+            d.record(*v);
+        }
+        d
+    }
+
+    fn to_json<F: FnOnce(&mut Vec<u8>) -> io::Result<()>>(write: F) -> Value {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn values_are_recorded_at_the_given_precision() {
+        let d = distribution(2, &[1.254, 0.5, -3.0, f64::NAN]);
+        assert_eq!(d.len(), 3);
+        assert_eq!(d.min(), 0.0);
+        assert_eq!(d.max(), 1.25);
+        assert_eq!(d.value_at_quantile(0.5), 0.5);
+
+        // with no decimals, values are rounded to integers
+        let d = distribution(0, &[0.4, 2.6]);
+        assert_eq!(d.quantiles(&[0.0, 1.0]), vec![0.0, 3.0]);
+    }
+
+    #[test]
+    fn merge_matches_recording_sequentially() {
+        let values: Vec<f64> = (0..100).map(|v| v as f64 / 7.0).collect();
+        let all = distribution(3, &values);
+        let mut merged = distribution(3, &values[..40]);
+        merged.merge(&distribution(3, &values[40..]));
+        assert_eq!(merged.len(), all.len());
+        assert_eq!(merged.mean(), all.mean());
+        assert_eq!(
+            merged.quantiles(&COMMON_QUANTILES),
+            all.quantiles(&COMMON_QUANTILES)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "different precisions")]
+    fn merge_rejects_other_precisions() {
+        Distribution::new(2, 3).merge(&Distribution::new(3, 3));
+    }
+
+    #[test]
+    fn cdf_has_a_point_per_recorded_value() {
+        let d = distribution(0, &[1.0, 1.0, 2.0, 4.0]);
+        assert_eq!(d.cdf(), vec![(1.0, 0.5), (2.0, 0.75), (4.0, 1.0)]);
+        assert!(Distribution::default().cdf().is_empty());
+    }
+
+    #[test]
+    fn writes_csv_and_json() {
+        let d = distribution(0, &[1.0, 1.0, 2.0, 4.0]);
+        let mut csv = Vec::new();
+        d.write_quantiles_csv(&mut csv, &[0.5, 1.0]).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "count,mean,0.5,1\n4,2,1,4\n"
+        );
+        let mut csv = Vec::new();
+        d.write_cdf_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "value,fraction\n1,0.5\n2,0.75\n4,1\n"
+        );
+        assert_eq!(
+            to_json(|w| d.write_quantiles_json(w, &[0.5])),
+            json!({
+                "count": 4,
+                "mean": 2.0,
+                "quantiles": [{"quantile": 0.5, "value": 1.0}],
+            })
+        );
+        assert_eq!(
+            to_json(|w| d.write_cdf_json(w)),
+            json!({
+                "count": 4,
+                "cdf": [
+                    {"value": 1.0, "fraction": 0.5},
+                    {"value": 2.0, "fraction": 0.75},
+                    {"value": 4.0, "fraction": 1.0},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn groups_merge_and_combine() {
+        let mut a = GroupedDistribution::new(0, 3);
+        a.record("x", 1.0);
+        a.record("y", 2.0);
+        let mut b = GroupedDistribution::new(0, 3);
+        b.record("y", 4.0);
+        b.record("z", 8.0);
+        a.merge(b);
+        assert_eq!(a.len(), 3);
+        assert_eq!(a.get(&"y").unwrap().quantiles(&[0.0, 1.0]), vec![2.0, 4.0]);
+        assert!(a.get(&"w").is_none());
+        let all = a.combined();
+        assert_eq!((all.len(), all.mean()), (4, 3.75));
+        let rows = to_json(|w| a.write_quantiles_json(w, &[1.0]));
+        let rows: Vec<_> = rows
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["key"].clone(),
+                    r["count"].clone(),
+                    r["quantiles"][0]["value"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (json!("x"), json!(1), json!(1.0)),
+                (json!("y"), json!(2), json!(4.0)),
+                (json!("z"), json!(1), json!(8.0)),
+            ]
+        );
+        let mut csv = Vec::new();
+        a.write_cdf_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "key,value,fraction\nx,1,1\ny,2,0.5\ny,4,1\nz,8,1\n"
+        );
+    }
+
+    #[test]
+    fn bands_key_grouped_distributions() {
+        let mut d = GroupedDistribution::new(3, 3);
+        d.record(PriorityBand::of(9), 2.0);
+        d.record(PriorityBand::of(0), 1.0);
+        let mut csv = Vec::new();
+        d.write_quantiles_csv(&mut csv, &[0.5]).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "key,count,mean,0.5\nfree,1,1,1\nproduction,1,2,2\n"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors raised while reading the trace's tables or caches of them, or exporting them.
+#[derive(Debug)]
+pub enum Error {
+    /// A part file could not be opened or read.
+    Io { path: PathBuf, source: io::Error },
+    /// A part file is not well-formed CSV (e.g., invalid UTF-8 or a ragged row).
+    Csv {
+        path: PathBuf,
+        line: Option<u64>,
+        source: csv::Error,
+    },
+    /// A record does not match the table's schema (e.g., a missing mandatory field or an unknown
+    /// enum value).
+    Schema {
+        path: PathBuf,
+        line: Option<u64>,
+        source: csv::Error,
+    },
+    /// A table directory does not hold a complete and consistent set of part files.
+    PartFiles { path: PathBuf, reason: String },
+    /// A cache file is malformed, or does not hold the requested record type.
+    Cache { path: PathBuf, reason: String },
+    /// Writing a table to another format (e.g., Parquet or SQLite) failed.
+    Export { path: PathBuf, reason: String },
+}
+
+impl Error {
+    pub(crate) fn io(path: &Path, source: io::Error) -> Self {
+        Error::Io {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    pub(crate) fn part_files(path: &Path, reason: String) -> Self {
+        Error::PartFiles {
+            path: path.to_owned(),
+            reason,
+        }
+    }
+
+    pub(crate) fn cache(path: &Path, reason: String) -> Self {
+        Error::Cache {
+            path: path.to_owned(),
+            reason,
+        }
+    }
+
+    #[cfg(any(feature = "parquet", feature = "sqlite"))]
+    pub(crate) fn export(path: &Path, reason: String) -> Self {
+        Error::Export {
+            path: path.to_owned(),
+            reason,
+        }
+    }
+
+    /// Classifies an error raised by the CSV reader on the given part file.
+    pub(crate) fn csv(path: &Path, line: Option<u64>, source: csv::Error) -> Self {
+        let path = path.to_owned();
+        let line = line.or_else(|| source.position().map(|p| p.line()));
+        match source.kind() {
+            csv::ErrorKind::Io(_) => match source.into_kind() {
+                csv::ErrorKind::Io(source) => Error::Io { path, source },
+                _ => unreachable!(),
+            },
+            csv::ErrorKind::Deserialize { .. } => Error::Schema { path, line, source },
+            _ => Error::Csv { path, line, source },
+        }
+    }
+
+    /// The part file (or, for `PartFiles`, the table directory, for `Cache`, the cache file, and
+    /// for `Export`, the output file) in which the error occurred.
+    pub fn path(&self) -> &Path {
+        match self {
+            Error::Io { path, .. }
+            | Error::Csv { path, .. }
+            | Error::Schema { path, .. }
+            | Error::PartFiles { path, .. }
+            | Error::Cache { path, .. }
+            | Error::Export { path, .. } => path,
+        }
+    }
+
+    /// The line within the part file at which the error occurred, if known.
+    pub fn line(&self) -> Option<u64> {
+        match self {
+            Error::Io { .. }
+            | Error::PartFiles { .. }
+            | Error::Cache { .. }
+            | Error::Export { .. } => None,
+            Error::Csv { line, .. } | Error::Schema { line, .. } => *line,
+        }
+    }
+}
+
+fn write_at(
+    f: &mut fmt::Formatter,
+    path: &Path,
+    line: Option<u64>,
+    kind: &str,
+    source: &csv::Error,
+) -> fmt::Result {
+    match line {
+        Some(l) => write!(f, "{}:{}: {}: {}", path.display(), l, kind, source),
+        None => write!(f, "{}: {}: {}", path.display(), kind, source),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Csv { path, line, source } => write_at(f, path, *line, "malformed CSV", source),
+            Error::Schema { path, line, source } => {
+                write_at(f, path, *line, "schema mismatch", source)
+            }
+            Error::PartFiles { path, reason }
+            | Error::Cache { path, reason }
+            | Error::Export { path, reason } => {
+                write!(f, "{}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Csv { source, .. } | Error::Schema { source, .. } => Some(source),
+            Error::PartFiles { .. } | Error::Cache { .. } | Error::Export { .. } => None,
+        }
+    }
+}
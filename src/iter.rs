@@ -1,60 +1,499 @@
+use crate::common::TraceRecord;
+use crate::error::Error;
 use csv;
-use serde::de::DeserializeOwned;
-use std::fs::File;
+use flate2::read::MultiGzDecoder;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
 use std::iter::Iterator;
 use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// A position within a table, as reported by the table iterators' `current_position()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracePosition {
+    /// Index of the part file currently being read.
+    pub part: usize,
+    /// Number of part files in the table.
+    pub num_parts: usize,
+    /// Path of the part file currently being read.
+    pub path: PathBuf,
+    /// Number of records read from the current part file so far.
+    pub record: u64,
+}
+
+/// An iterator over the records in a single part file.
+pub struct PartFileIterator<T> {
+    path: PathBuf,
+    reader: csv::Reader<Box<dyn Read + Send>>,
+    record_num: u64,
+    done: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<T> PartFileIterator<T>
+where
+    T: TraceRecord,
+{
+    /// Opens a part file, streaming decompression if it is gzipped as shipped in the public
+    /// trace.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(|e| Error::io(path, e))?;
+        let input: Box<dyn Read + Send> = if path.extension() == Some("gz".as_ref()) {
+            Box::new(MultiGzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        // trace part files have no header row; the schema lives in a separate file
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(input);
+        Ok(PartFileIterator {
+            path: path.to_owned(),
+            reader,
+            record_num: 0,
+            done: false,
+            phantom: PhantomData,
+        })
+    }
+
+    /// The path of the part file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of records read from the part file so far.
+    pub fn records_read(&self) -> u64 {
+        self.record_num
+    }
+}
+
+impl<T> Iterator for PartFileIterator<T>
+where
+    T: TraceRecord,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut sr = csv::StringRecord::new();
+        match self.reader.read_record(&mut sr) {
+            Ok(true) => {
+                self.record_num += 1;
+                let line = sr.position().map(|p| p.line());
+                Some(
+                    sr.deserialize(None)
+                        .map_err(|e| Error::csv(&self.path, line, e)),
+                )
+            }
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                // the reader cannot recover from I/O (e.g., decompression) errors
+                self.done = e.is_io_error();
+                Some(Err(Error::csv(&self.path, None, e)))
+            }
+        }
+    }
+}
 
 pub(crate) struct TraceFileIterator<T> {
-    path: String,
+    files: Vec<PathBuf>,
     file_num: usize,
-    num_files: usize,
-    reader: csv::Reader<File>,
-    phantom: PhantomData<T>,
+    part: PartFileIterator<T>,
+    range: Range<u64>,
+    done: bool,
+}
+
+/// Parses a part file name of the form `part-NNNNN-of-MMMMM.csv[.gz]` into `(NNNNN, MMMMM)`.
+fn parse_part_name(name: &str) -> Option<(usize, usize)> {
+    let stem = name
+        .strip_suffix(".csv.gz")
+        .or_else(|| name.strip_suffix(".csv"))?;
+    let mut parts = stem.strip_prefix("part-")?.splitn(2, "-of-");
+    let i = parts.next()?.parse().ok()?;
+    let num = parts.next()?.parse().ok()?;
+    Some((i, num))
+}
+
+/// Finds the part files of the table stored in `dir`, in order, and checks that they form a
+/// complete set. Where both a plain and a gzipped copy of a part exist, the plain one is used.
+pub(crate) fn part_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut num_files = None;
+    let mut parts = BTreeMap::new();
+    for entry in fs::read_dir(dir).map_err(|e| Error::io(dir, e))? {
+        let path = entry.map_err(|e| Error::io(dir, e))?.path();
+        let (i, num) = match path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(parse_part_name)
+        {
+            Some(p) => p,
+            None => continue,
+        };
+        if *num_files.get_or_insert(num) != num {
+            return Err(Error::part_files(
+                dir,
+                format!(
+                    "part files disagree on the part count ({} vs. {})",
+                    num_files.unwrap(),
+                    num
+                ),
+            ));
+        }
+        if i >= num {
+            return Err(Error::part_files(
+                dir,
+                format!("part {} is out of range for {} parts", i, num),
+            ));
+        }
+        let plain = path.extension() == Some("csv".as_ref());
+        parts
+            .entry(i)
+            .and_modify(|p: &mut PathBuf| {
+                if plain {
+                    *p = path.clone();
+                }
+            })
+            .or_insert_with(|| path.clone());
+    }
+    let num_files = match num_files {
+        Some(n) => n,
+        None => return Err(Error::part_files(dir, "no part files found".to_owned())),
+    };
+    if parts.len() != num_files {
+        let missing: Vec<String> = (0..num_files)
+            .filter(|i| !parts.contains_key(i))
+            .map(|i| format!("{:05}", i))
+            .collect();
+        return Err(Error::part_files(
+            dir,
+            format!(
+                "{} of {} part files missing: {}",
+                missing.len(),
+                num_files,
+                missing.join(", ")
+            ),
+        ));
+    }
+    Ok(parts.into_values().collect())
 }
 
 impl<T> TraceFileIterator<T>
 where
-    T: DeserializeOwned,
+    T: TraceRecord,
 {
-    pub fn new(f: &str, num_files: usize) -> Self {
-        TraceFileIterator {
-            path: f.to_owned(),
+    /// Creates an iterator over the records in all part files of the table stored in `dir`.
+    pub fn new(dir: &Path) -> Result<Self, Error> {
+        let files = part_files(dir)?;
+        let part = PartFileIterator::open(&files[0])?;
+        Ok(TraceFileIterator {
+            files,
             file_num: 0,
-            num_files: num_files,
-            reader: csv::Reader::from_path(Self::filename(f, 0, num_files)).unwrap(),
-            phantom: PhantomData,
+            part,
+            range: 0..u64::MAX,
+            done: false,
+        })
+    }
+
+    /// Creates an iterator over the records with timestamps in `[start, end)` in the table stored
+    /// in `dir`.
+    ///
+    /// Since part files hold consecutive time ranges, this binary-searches the parts by their
+    /// first timestamps to skip those that end before `start`, and stops at the first record at
+    /// or after `end`.
+    pub fn range(dir: &Path, start: u64, end: u64) -> Result<Self, Error> {
+        let files = part_files(dir)?;
+        // find the number of parts that begin strictly before `start`; records at `start` may
+        // still be at the end of the last of these.
+        let (mut lo, mut hi) = (0, files.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            // an empty part tells us nothing, so we conservatively search further left
+            match Self::first_time(&files[mid])? {
+                Some(t) if t < start => lo = mid + 1,
+                _ => hi = mid,
+            }
         }
+        let file_num = lo.saturating_sub(1);
+        let part = PartFileIterator::open(&files[file_num])?;
+        Ok(TraceFileIterator {
+            files,
+            file_num,
+            part,
+            range: start..end,
+            done: false,
+        })
     }
 
-    fn filename(path: &str, i: usize, num: usize) -> String {
-        format!("{}/part-{:05}-of-{:05}.csv", path, i, num)
+    /// Reads the timestamp of the first record in a part file, or `None` if it is empty.
+    fn first_time(path: &Path) -> Result<Option<u64>, Error> {
+        PartFileIterator::<T>::open(path)?
+            .next()
+            .transpose()
+            .map(|r| r.map(|rec| rec.time()))
     }
 
-    fn next_file(&mut self) -> Option<()> {
-        if self.file_num >= self.num_files {
+    /// Moves on to the next part file, or returns `None` if the current one is the last.
+    fn next_file(&mut self) -> Option<Result<(), Error>> {
+        if self.file_num + 1 >= self.files.len() {
             None
         } else {
             self.file_num += 1;
-            self.reader =
-                csv::Reader::from_path(Self::filename(&self.path, self.file_num, self.num_files))
-                    .unwrap();
-            Some(())
+            // on failure, we keep the exhausted part so that the next call moves on
+            Some(PartFileIterator::open(&self.files[self.file_num]).map(|p| self.part = p))
+        }
+    }
+
+    /// The index of the part file that the last record came from.
+    pub fn current_part(&self) -> usize {
+        self.file_num
+    }
+
+    /// The part file and record offset the iterator has reached.
+    pub fn current_position(&self) -> TracePosition {
+        TracePosition {
+            part: self.current_part(),
+            num_parts: self.files.len(),
+            path: self.files[self.file_num].clone(),
+            record: self.part.records_read(),
         }
     }
 }
 
 impl<T> Iterator for TraceFileIterator<T>
 where
-    T: DeserializeOwned,
+    T: TraceRecord,
 {
-    type Item = Result<T, csv::Error>;
+    type Item = Result<T, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut sr = csv::StringRecord::new();
-        if !self.reader.read_record(&mut sr).unwrap() {
-            self.next_file().and(self.next())
-        } else {
-            Some(sr.deserialize(None))
+        if self.done {
+            return None;
+        }
+        loop {
+            match self.part.next() {
+                Some(Ok(rec)) if rec.time() < self.range.start => continue,
+                Some(Ok(rec)) if rec.time() >= self.range.end => {
+                    self.done = true;
+                    return None;
+                }
+                Some(r) => return Some(r),
+                None => {
+                    if let Err(e) = self.next_file()? {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine_events::MachineEvent;
+    use crate::testing::{scratch_dir, write_part};
+
+    fn machine_events(times: &[u64]) -> Vec<String> {
+        times.iter().map(|t| format!("{},1,2,,,", t)).collect()
+    }
+
+    /// Writes a machine_events part file holding events at `times`.
+    fn write_events(trace: &Path, name: &str, times: &[u64]) {
+        let rows = machine_events(times);
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        write_part(trace, "machine_events", name, &rows);
+    }
+
+    fn part_files_error(dir: &Path) -> String {
+        match part_files(dir) {
+            Err(Error::PartFiles { reason, .. }) => reason,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(files) => panic!("accepted part files {:?}", files),
         }
     }
+
+    fn file_names(files: &[PathBuf]) -> Vec<&str> {
+        files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn part_names() {
+        assert_eq!(
+            parse_part_name("part-00003-of-00500.csv.gz"),
+            Some((3, 500))
+        );
+        assert_eq!(parse_part_name("part-00000-of-00001.csv"), Some((0, 1)));
+        assert_eq!(parse_part_name("part-00000-of-00001.csv.tmp"), None);
+        assert_eq!(parse_part_name("schema.csv"), None);
+    }
+
+    #[test]
+    fn parts_are_discovered_in_order() {
+        let trace = scratch_dir();
+        write_events(&trace, "part-00002-of-00003.csv.gz", &[]);
+        write_events(&trace, "part-00000-of-00003.csv.gz", &[]);
+        write_events(&trace, "part-00001-of-00003.csv", &[]);
+        // other files are ignored
+        write_events(&trace, "SHA256SUM", &[]);
+        let files = part_files(&trace.join("machine_events")).unwrap();
+        assert_eq!(
+            file_names(&files),
+            vec![
+                "part-00000-of-00003.csv.gz",
+                "part-00001-of-00003.csv",
+                "part-00002-of-00003.csv.gz"
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_parts_are_preferred() {
+        let trace = scratch_dir();
+        write_events(&trace, "part-00000-of-00002.csv", &[]);
+        write_events(&trace, "part-00000-of-00002.csv.gz", &[]);
+        write_events(&trace, "part-00001-of-00002.csv.gz", &[]);
+        write_events(&trace, "part-00001-of-00002.csv", &[]);
+        let files = part_files(&trace.join("machine_events")).unwrap();
+        assert_eq!(
+            file_names(&files),
+            vec!["part-00000-of-00002.csv", "part-00001-of-00002.csv"]
+        );
+    }
+
+    #[test]
+    fn incomplete_parts_are_rejected() {
+        let trace = scratch_dir();
+        write_events(&trace, "part-00000-of-00004.csv", &[]);
+        write_events(&trace, "part-00002-of-00004.csv", &[]);
+        assert_eq!(
+            part_files_error(&trace.join("machine_events")),
+            "2 of 4 part files missing: 00001, 00003"
+        );
+    }
+
+    #[test]
+    fn inconsistent_parts_are_rejected() {
+        let trace = scratch_dir();
+        write_events(&trace, "part-00000-of-00002.csv", &[]);
+        write_events(&trace, "part-00001-of-00003.csv", &[]);
+        let reason = part_files_error(&trace.join("machine_events"));
+        assert!(reason.starts_with("part files disagree on the part count"));
+
+        let trace = scratch_dir();
+        write_events(&trace, "part-00000-of-00001.csv", &[]);
+        write_events(&trace, "part-00001-of-00001.csv", &[]);
+        let reason = part_files_error(&trace.join("machine_events"));
+        assert_eq!(reason, "part 1 is out of range for 1 parts");
+
+        let trace = scratch_dir();
+        write_events(&trace, "SHA256SUM", &[]);
+        assert_eq!(
+            part_files_error(&trace.join("machine_events")),
+            "no part files found"
+        );
+    }
+
+    fn times(records: TraceFileIterator<MachineEvent>) -> Vec<u64> {
+        records.map(|r| r.unwrap().time).collect()
+    }
+
+    #[test]
+    fn tables_end_cleanly() {
+        let trace = scratch_dir();
+        write_events(&trace, "part-00000-of-00003.csv", &[0, 10]);
+        write_events(&trace, "part-00001-of-00003.csv.gz", &[20]);
+        // an empty last part
+        write_events(&trace, "part-00002-of-00003.csv", &[]);
+        let dir = trace.join("machine_events");
+        assert_eq!(
+            times(TraceFileIterator::new(&dir).unwrap()),
+            vec![0, 10, 20]
+        );
+
+        let mut records = TraceFileIterator::<MachineEvent>::new(&dir).unwrap();
+        assert_eq!(records.by_ref().count(), 3);
+        assert!(records.next().is_none());
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn positions_follow_parts() {
+        let trace = scratch_dir();
+        write_events(&trace, "part-00000-of-00002.csv", &[0, 10]);
+        write_events(&trace, "part-00001-of-00002.csv.gz", &[20, 30]);
+        let dir = trace.join("machine_events");
+        let mut records = TraceFileIterator::<MachineEvent>::new(&dir).unwrap();
+        let names = ["part-00000-of-00002.csv", "part-00001-of-00002.csv.gz"];
+        let position = |records: &TraceFileIterator<MachineEvent>| {
+            let p = records.current_position();
+            assert_eq!(p.num_parts, 2);
+            assert_eq!(p.path, dir.join(names[p.part]));
+            (p.part, p.record)
+        };
+
+        assert_eq!(position(&records), (0, 0));
+        records.next().unwrap().unwrap();
+        assert_eq!(position(&records), (0, 1));
+        records.next().unwrap().unwrap();
+        assert_eq!(position(&records), (0, 2));
+        // the first record of the second part
+        assert_eq!(records.next().unwrap().unwrap().time, 20);
+        assert_eq!(position(&records), (1, 1));
+        records.next().unwrap().unwrap();
+        assert_eq!(position(&records), (1, 2));
+        assert!(records.next().is_none());
+        assert_eq!(position(&records), (1, 2));
+    }
+
+    /// A table whose second part starts with the timestamp that ends the first.
+    fn ranged_table() -> PathBuf {
+        let trace = scratch_dir();
+        write_events(&trace, "part-00000-of-00003.csv", &[0, 10, 20, 30]);
+        write_events(&trace, "part-00001-of-00003.csv.gz", &[30, 40, 50]);
+        write_events(&trace, "part-00002-of-00003.csv", &[60, 70]);
+        trace.join("machine_events")
+    }
+
+    fn range(dir: &Path, start: u64, end: u64) -> TraceFileIterator<MachineEvent> {
+        TraceFileIterator::range(dir, start, end).unwrap()
+    }
+
+    #[test]
+    fn range_starts_mid_part() {
+        let dir = ranged_table();
+        assert_eq!(times(range(&dir, 15, 45)), vec![20, 30, 30, 40]);
+        let records = range(&dir, 45, 65);
+        assert_eq!(records.current_part(), 1);
+        assert_eq!(times(records), vec![50, 60]);
+        assert_eq!(times(range(&dir, 0, 10)), vec![0]);
+    }
+
+    #[test]
+    fn range_starts_on_part_boundary() {
+        let dir = ranged_table();
+        // records at the start time may end the previous part
+        assert_eq!(times(range(&dir, 30, 41)), vec![30, 30, 40]);
+        let records = range(&dir, 60, u64::MAX);
+        assert_eq!(records.current_part(), 1);
+        assert_eq!(times(records), vec![60, 70]);
+    }
+
+    #[test]
+    fn range_past_the_end() {
+        let dir = ranged_table();
+        assert!(times(range(&dir, 71, u64::MAX)).is_empty());
+        assert!(times(range(&dir, 1000, 2000)).is_empty());
+        // an empty range
+        assert!(times(range(&dir, 40, 40)).is_empty());
+    }
 }
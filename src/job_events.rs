@@ -1,7 +1,7 @@
-use crate::common::{MissingInfo, SchedulingClass};
-use crate::iter::TraceFileIterator;
-
-pub(crate) static JOB_EVENT_FILE_COUNT: usize = 500;
+use crate::common::{MissingInfo, SchedulingClass, TraceRecord};
+use crate::error::Error;
+use crate::iter::{TraceFileIterator, TracePosition};
+use std::path::Path;
 
 // 1,time,INTEGER,YES
 // 2,missing info,INTEGER,NO
@@ -11,7 +11,7 @@ pub(crate) static JOB_EVENT_FILE_COUNT: usize = 500;
 // 6,scheduling class,INTEGER,NO
 // 7,job name,STRING_HASH,NO
 // 8,logical job name,STRING_HASH,NO
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct JobEvent {
     pub time: u64,
     pub missing_info: Option<MissingInfo>,
@@ -23,7 +23,15 @@ pub struct JobEvent {
     pub logical_job_name: Option<String>,
 }
 
-#[derive(Debug, Deserialize_repr, PartialEq)]
+impl TraceRecord for JobEvent {
+    const TABLE: &'static str = "job_events";
+
+    fn time(&self) -> u64 {
+        self.time
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize_repr, Serialize_repr, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum JobEventType {
     /// SUBMIT (0): A task or job became eligible for scheduling.
@@ -61,16 +69,30 @@ pub struct JobEventIterator {
 }
 
 impl JobEventIterator {
-    pub fn new(trace_path: &str) -> Self {
-        let fp = format!("{}/job_events/", trace_path);
-        JobEventIterator {
-            file_iter: TraceFileIterator::new(&fp, JOB_EVENT_FILE_COUNT),
-        }
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(JobEvent::TABLE);
+        Ok(JobEventIterator {
+            file_iter: TraceFileIterator::new(&fp)?,
+        })
+    }
+
+    /// Iterates over the records with timestamps in `[start, end)`, skipping part files that
+    /// end before `start`.
+    pub fn range(trace_path: &str, start: u64, end: u64) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(JobEvent::TABLE);
+        Ok(JobEventIterator {
+            file_iter: TraceFileIterator::range(&fp, start, end)?,
+        })
+    }
+
+    /// The part file and record offset the iterator has reached.
+    pub fn current_position(&self) -> TracePosition {
+        self.file_iter.current_position()
     }
 }
 
 impl Iterator for JobEventIterator {
-    type Item = Result<JobEvent, csv::Error>;
+    type Item = Result<JobEvent, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.file_iter.next()
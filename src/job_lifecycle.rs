@@ -0,0 +1,241 @@
+use crate::common::{SchedulingClass, AFTER_TRACE_TIME};
+use crate::error::Error;
+use crate::job_events::{JobEvent, JobEventType};
+use crate::merge::{MergedEventIterator, Table, TraceEvent};
+use crate::task_events::{TaskEvent, TaskEventType};
+use crate::task_lifecycle::{TaskLifecycleState, TaskStateMachine};
+use std::collections::HashMap;
+
+/// A summary of a job's life in the trace, combined from its job and task events.
+#[derive(Clone, Debug)]
+pub struct JobLifecycle {
+    pub job_id: u64,
+    /// The time of the job's SUBMIT event, if the trace has one.
+    pub submit_time: Option<u64>,
+    /// The time at which the job or any of its tasks was first scheduled.
+    pub first_schedule_time: Option<u64>,
+    /// The time of the job's terminal event, if it terminated within the trace; `AFTER_TRACE_TIME`
+    /// if it terminated after the trace window.
+    pub end_time: Option<u64>,
+    pub terminal_event: Option<JobEventType>,
+    /// The number of distinct tasks seen for the job.
+    pub task_count: u64,
+    pub evictions: u32,
+    pub failures: u32,
+    /// The number of times any of the job's tasks was submitted again after terminating.
+    pub resubmissions: u32,
+    pub user: Option<String>,
+    pub scheduling_class: Option<SchedulingClass>,
+    pub logical_job_name: Option<String>,
+}
+
+impl JobLifecycle {
+    fn new(job_id: u64) -> Self {
+        JobLifecycle {
+            job_id,
+            submit_time: None,
+            first_schedule_time: None,
+            end_time: None,
+            terminal_event: None,
+            task_count: 0,
+            evictions: 0,
+            failures: 0,
+            resubmissions: 0,
+            user: None,
+            scheduling_class: None,
+            logical_job_name: None,
+        }
+    }
+
+    /// The time from the job's submission to its termination, if both are in the trace window.
+    pub fn duration(&self) -> Option<u64> {
+        let end_time = self.end_time.filter(|t| *t < AFTER_TRACE_TIME)?;
+        end_time.checked_sub(self.submit_time?)
+    }
+
+    /// The time from the job's submission until it was first scheduled, if both are in the
+    /// trace.
+    pub fn scheduling_delay(&self) -> Option<u64> {
+        self.first_schedule_time?.checked_sub(self.submit_time?)
+    }
+
+    fn scheduled_at(&mut self, time: u64) {
+        self.first_schedule_time = Some(self.first_schedule_time.map_or(time, |t| t.min(time)));
+    }
+}
+
+/// Builds a `JobLifecycle` for every job from job and task events fed to it in time order.
+#[derive(Debug, Default)]
+pub struct JobLifecycleBuilder {
+    jobs: HashMap<u64, JobLifecycle>,
+    tasks: TaskStateMachine,
+}
+
+impl JobLifecycleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the lifecycles of all jobs in the trace at `trace_path`.
+    pub fn from_trace(trace_path: &str) -> Result<HashMap<u64, JobLifecycle>, Error> {
+        let mut builder = JobLifecycleBuilder::new();
+        let events = MergedEventIterator::new(trace_path, &[Table::JobEvents, Table::TaskEvents])?;
+        for ev in events {
+            builder.add_event(&ev?);
+        }
+        Ok(builder.build())
+    }
+
+    /// Adds a job or task event; other events are ignored.
+    pub fn add_event(&mut self, event: &TraceEvent) {
+        match event {
+            TraceEvent::Job(e) => self.add_job_event(e),
+            TraceEvent::Task(e) => self.add_task_event(e),
+            _ => (),
+        }
+    }
+
+    pub fn add_job_event(&mut self, e: &JobEvent) {
+        let job = self
+            .jobs
+            .entry(e.job_id)
+            .or_insert_with(|| JobLifecycle::new(e.job_id));
+        match e.event_type {
+            JobEventType::Submit => {
+                job.submit_time = job.submit_time.or(Some(e.time));
+            }
+            JobEventType::Schedule => job.scheduled_at(e.time),
+            JobEventType::Evict
+            | JobEventType::Fail
+            | JobEventType::Finish
+            | JobEventType::Kill
+            | JobEventType::Lost => {
+                job.end_time = Some(e.time);
+                job.terminal_event = Some(e.event_type);
+            }
+            JobEventType::UpdatePending | JobEventType::UpdateRunning => (),
+        }
+        if e.user.is_some() {
+            job.user = e.user.clone();
+        }
+        if e.logical_job_name.is_some() {
+            job.logical_job_name = e.logical_job_name.clone();
+        }
+        job.scheduling_class = e.scheduling_class.or(job.scheduling_class);
+    }
+
+    pub fn add_task_event(&mut self, e: &TaskEvent) {
+        let job = self
+            .jobs
+            .entry(e.job_id)
+            .or_insert_with(|| JobLifecycle::new(e.job_id));
+        if self.tasks.state(e.job_id, e.task_index) == TaskLifecycleState::Unsubmitted {
+            job.task_count += 1;
+        }
+        match e.event_type {
+            TaskEventType::Schedule => job.scheduled_at(e.time),
+            TaskEventType::Evict => job.evictions += 1,
+            TaskEventType::Fail => job.failures += 1,
+            _ => (),
+        }
+        // illegal transitions still count towards the task's lifecycle, but cannot be
+        // resubmissions, which are always legal
+        if let Ok(t) = self.tasks.apply(e) {
+            if t.is_resubmission() {
+                job.resubmissions += 1;
+            }
+        }
+    }
+
+    /// The lifecycle of a job as built so far.
+    pub fn get(&self, job_id: u64) -> Option<&JobLifecycle> {
+        self.jobs.get(&job_id)
+    }
+
+    /// Returns the lifecycles of all jobs seen, keyed by job ID.
+    pub fn build(self) -> HashMap<u64, JobLifecycle> {
+        self.jobs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse;
+
+    fn job_event(time: u64, job_id: u64, event_type: u8) -> TraceEvent {
+        TraceEvent::Job(parse(&format!(
+            "{},,{},{},u1,1,j{},l{}",
+            time, job_id, event_type, job_id, job_id
+        )))
+    }
+
+    fn task_event(time: u64, job_id: u64, task_index: u64, event_type: u8) -> TraceEvent {
+        TraceEvent::Task(parse(&format!(
+            "{},,{},{},,{},u1,1,9,,,,0",
+            time, job_id, task_index, event_type
+        )))
+    }
+
+    fn build(events: &[TraceEvent]) -> HashMap<u64, JobLifecycle> {
+        let mut builder = JobLifecycleBuilder::new();
+        for e in events {
+            builder.add_event(e);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn jobs_combine_their_task_events() {
+        let jobs = build(&[
+            job_event(5, 1, 0),
+            task_event(10, 1, 0, 0),
+            task_event(10, 1, 1, 0),
+            task_event(20, 1, 0, 1),
+            job_event(25, 1, 1),
+            task_event(30, 1, 0, 2),
+            task_event(30, 1, 1, 1),
+            task_event(40, 1, 0, 0),
+            task_event(40, 1, 1, 3),
+            task_event(45, 1, 1, 0),
+            task_event(50, 1, 0, 1),
+            task_event(50, 1, 1, 1),
+            task_event(60, 1, 0, 4),
+            task_event(70, 1, 1, 4),
+            job_event(80, 1, 4),
+        ]);
+        let job = &jobs[&1];
+        assert_eq!(job.task_count, 2);
+        assert_eq!((job.evictions, job.failures, job.resubmissions), (1, 1, 2));
+        assert_eq!(job.first_schedule_time, Some(20));
+        assert_eq!(job.scheduling_delay(), Some(15));
+        assert_eq!(job.terminal_event, Some(JobEventType::Finish));
+        assert_eq!(job.duration(), Some(75));
+        assert_eq!(job.logical_job_name.as_deref(), Some("l1"));
+    }
+
+    #[test]
+    fn partial_jobs_have_no_duration() {
+        let jobs = build(&[
+            // a task of a job submitted before the trace, first seen being scheduled
+            task_event(10, 2, 0, 1),
+            task_event(20, 2, 0, 8),
+            job_event(30, 3, 0),
+            task_event(30, 3, 0, 0),
+            task_event(40, 3, 0, 1),
+            task_event(AFTER_TRACE_TIME, 3, 0, 4),
+            job_event(AFTER_TRACE_TIME, 3, 4),
+        ]);
+        let job = &jobs[&2];
+        assert_eq!(job.task_count, 1);
+        assert_eq!(job.first_schedule_time, Some(10));
+        assert_eq!((job.submit_time, job.scheduling_delay()), (None, None));
+        assert_eq!((job.end_time, job.duration()), (None, None));
+
+        let job = &jobs[&3];
+        assert_eq!(job.scheduling_delay(), Some(10));
+        assert_eq!(job.end_time, Some(AFTER_TRACE_TIME));
+        assert_eq!(job.terminal_event, Some(JobEventType::Finish));
+        assert_eq!(job.duration(), None);
+    }
+}
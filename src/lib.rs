@@ -4,10 +4,37 @@ extern crate serde_derive;
 #[macro_use]
 extern crate serde_repr;
 
+pub mod attribute_store;
+pub mod cache;
+pub mod cluster_state;
 pub mod common;
+pub mod constraint_checker;
+pub mod distribution;
+pub mod error;
 pub mod iter;
 pub mod job_events;
+pub mod job_lifecycle;
 pub mod machine_attributes;
 pub mod machine_events;
+pub mod machine_timeline;
+pub mod merge;
+pub mod par;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod replay;
+pub mod schema;
+pub mod sim;
+pub mod slack;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod task_attempts;
+pub mod task_constraints;
 pub mod task_events;
+pub mod task_lifecycle;
 pub mod task_usage;
+#[cfg(test)]
+mod testing;
+pub mod usage_join;
+pub mod utilization;
+
+pub use crate::error::Error;
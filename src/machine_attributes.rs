@@ -1,31 +1,62 @@
+use crate::common::{bool_from_int, bool_to_int, AttributeValue, TraceRecord};
+use crate::error::Error;
+use crate::iter::{TraceFileIterator, TracePosition};
+use std::path::Path;
+
 // 1,time,INTEGER,YES
 // 2,machine ID,INTEGER,YES
 // 3,attribute name,STRING_HASH,YES
 // 4,attribute value,STRING_HASH_OR_INTEGER,NO
 // 5,attribute deleted,BOOLEAN,YES
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MachineAttribute {
     pub time: u64,
     pub machine_id: u64,
     pub attribute_name: String,
-    pub attribute_value: Option<String>,
-    pub attributed_deleted: bool,
+    pub attribute_value: Option<AttributeValue>,
+    #[serde(deserialize_with = "bool_from_int", serialize_with = "bool_to_int")]
+    pub attribute_deleted: bool,
+}
+
+impl TraceRecord for MachineAttribute {
+    const TABLE: &'static str = "machine_attributes";
+
+    fn time(&self) -> u64 {
+        self.time
+    }
+}
+
+pub struct MachineAttributeIterator {
+    file_iter: TraceFileIterator<MachineAttribute>,
 }
 
-pub fn for_each_in_file<F>(file: &str, mut f: F) -> std::io::Result<()>
-where
-    F: FnMut(MachineAttribute) -> std::io::Result<()>,
-{
-    use std::fs::File;
+impl MachineAttributeIterator {
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(MachineAttribute::TABLE);
+        Ok(MachineAttributeIterator {
+            file_iter: TraceFileIterator::new(&fp)?,
+        })
+    }
+
+    /// Iterates over the records with timestamps in `[start, end)`, skipping part files that
+    /// end before `start`.
+    pub fn range(trace_path: &str, start: u64, end: u64) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(MachineAttribute::TABLE);
+        Ok(MachineAttributeIterator {
+            file_iter: TraceFileIterator::range(&fp, start, end)?,
+        })
+    }
 
-    let file = File::open(file)?;
-    let mut rdr = csv::Reader::from_reader(file);
+    /// The part file and record offset the iterator has reached.
+    pub fn current_position(&self) -> TracePosition {
+        self.file_iter.current_position()
+    }
+}
 
-    for result in rdr.records() {
-        let sr = result?;
-        let machine_attrib: MachineAttribute = sr.deserialize(None)?;
+impl Iterator for MachineAttributeIterator {
+    type Item = Result<MachineAttribute, Error>;
 
-        f(machine_attrib)?
+    fn next(&mut self) -> Option<Self::Item> {
+        self.file_iter.next()
     }
-    Ok(())
 }
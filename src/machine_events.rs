@@ -1,6 +1,7 @@
-use crate::iter::TraceFileIterator;
-
-pub(crate) static MACHINE_EVENT_FILE_COUNT: usize = 1;
+use crate::common::TraceRecord;
+use crate::error::Error;
+use crate::iter::{TraceFileIterator, TracePosition};
+use std::path::Path;
 
 // 1,time,INTEGER,YES
 // 2,machine ID,INTEGER,YES
@@ -8,7 +9,7 @@ pub(crate) static MACHINE_EVENT_FILE_COUNT: usize = 1;
 // 4,platform ID,STRING_HASH,NO
 // 5,CPUs,FLOAT,NO
 // 6,Memory,FLOAT,NO
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MachineEvent {
     pub time: u64,
     pub machine_id: u64,
@@ -18,7 +19,15 @@ pub struct MachineEvent {
     pub memory: Option<f64>,
 }
 
-#[derive(Debug, Deserialize_repr, PartialEq)]
+impl TraceRecord for MachineEvent {
+    const TABLE: &'static str = "machine_events";
+
+    fn time(&self) -> u64 {
+        self.time
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize_repr, Serialize_repr, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum MachineEventType {
     /// ADD (0): a machine became available to the cluster
@@ -29,13 +38,13 @@ pub enum MachineEventType {
     Update = 2,
 }
 
-impl Into<MachineEventType> for &str {
-    fn into(self) -> MachineEventType {
-        if self == "0" {
+impl From<&str> for MachineEventType {
+    fn from(s: &str) -> MachineEventType {
+        if s == "0" {
             MachineEventType::Add
-        } else if self == "1" {
+        } else if s == "1" {
             MachineEventType::Remove
-        } else if self == "2" {
+        } else if s == "2" {
             MachineEventType::Update
         } else {
             unreachable!()
@@ -48,16 +57,30 @@ pub struct MachineEventIterator {
 }
 
 impl MachineEventIterator {
-    pub fn new(trace_path: &str) -> Self {
-        let fp = format!("{}/machine_events/", trace_path);
-        MachineEventIterator {
-            file_iter: TraceFileIterator::new(&fp, MACHINE_EVENT_FILE_COUNT),
-        }
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(MachineEvent::TABLE);
+        Ok(MachineEventIterator {
+            file_iter: TraceFileIterator::new(&fp)?,
+        })
+    }
+
+    /// Iterates over the records with timestamps in `[start, end)`, skipping part files that
+    /// end before `start`.
+    pub fn range(trace_path: &str, start: u64, end: u64) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(MachineEvent::TABLE);
+        Ok(MachineEventIterator {
+            file_iter: TraceFileIterator::range(&fp, start, end)?,
+        })
+    }
+
+    /// The part file and record offset the iterator has reached.
+    pub fn current_position(&self) -> TracePosition {
+        self.file_iter.current_position()
     }
 }
 
 impl Iterator for MachineEventIterator {
-    type Item = Result<MachineEvent, csv::Error>;
+    type Item = Result<MachineEvent, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.file_iter.next()
@@ -0,0 +1,260 @@
+use crate::error::Error;
+use crate::machine_events::{MachineEvent, MachineEventIterator, MachineEventType};
+use std::collections::HashMap;
+
+/// A machine's normalized resource capacity. Capacities missing from the trace count as zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Capacity {
+    pub cpus: f64,
+    pub memory: f64,
+}
+
+/// Each machine's capacity over time, built from the machine events table.
+///
+/// A machine's capacity is a step function: each step holds from its timestamp until the next
+/// step, and a `None` step means the machine is not part of the cluster.
+#[derive(Clone, Debug, Default)]
+pub struct MachineTimeline {
+    machines: HashMap<u64, Vec<(u64, Option<Capacity>)>>,
+}
+
+impl MachineTimeline {
+    /// Builds the timeline from the machine events of the trace at `trace_path`.
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        Self::from_events(MachineEventIterator::new(trace_path)?)
+    }
+
+    /// Builds the timeline from machine events in time order.
+    pub fn from_events<I>(events: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Result<MachineEvent, Error>>,
+    {
+        let mut timeline = MachineTimeline::default();
+        for e in events {
+            timeline.apply(&e?);
+        }
+        Ok(timeline)
+    }
+
+    fn apply(&mut self, e: &MachineEvent) {
+        let steps = self.machines.entry(e.machine_id).or_default();
+        let current = steps.last().and_then(|(_, c)| *c);
+        let next = match e.event_type {
+            MachineEventType::Add => Some(Capacity {
+                cpus: e.cpus.unwrap_or(0.0),
+                memory: e.memory.unwrap_or(0.0),
+            }),
+            MachineEventType::Remove => None,
+            MachineEventType::Update => {
+                let c = current.unwrap_or_default();
+                Some(Capacity {
+                    cpus: e.cpus.unwrap_or(c.cpus),
+                    memory: e.memory.unwrap_or(c.memory),
+                })
+            }
+        };
+        // several events at the same timestamp collapse into one step
+        match steps.last_mut() {
+            Some((t, c)) if *t == e.time => *c = next,
+            _ => steps.push((e.time, next)),
+        }
+    }
+
+    /// The IDs of all machines that appear in the trace.
+    pub fn machine_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.machines.keys().cloned()
+    }
+
+    /// The steps of a machine's capacity over time.
+    pub fn machine_steps(&self, machine_id: u64) -> &[(u64, Option<Capacity>)] {
+        self.machines
+            .get(&machine_id)
+            .map_or(&[], |steps| steps.as_slice())
+    }
+
+    /// A machine's capacity at `time`, or `None` if it was not part of the cluster then.
+    pub fn capacity_at(&self, machine_id: u64, time: u64) -> Option<Capacity> {
+        let steps = self.machine_steps(machine_id);
+        let i = steps.partition_point(|(t, _)| *t <= time);
+        steps[..i].last().and_then(|(_, c)| *c)
+    }
+
+    /// The intervals during which a machine was removed from the cluster, as `(remove, re-add)`
+    /// times; the re-add time is `None` if the machine never returned.
+    pub fn downtime(&self, machine_id: u64) -> Vec<(u64, Option<u64>)> {
+        let mut intervals = Vec::new();
+        let mut removed_at = None;
+        for (t, c) in self.machine_steps(machine_id) {
+            match (c, removed_at) {
+                (None, None) => removed_at = Some(*t),
+                (Some(_), Some(r)) => {
+                    intervals.push((r, Some(*t)));
+                    removed_at = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(r) = removed_at {
+            intervals.push((r, None));
+        }
+        intervals
+    }
+
+    /// The total capacity of the cluster at `time`.
+    pub fn cluster_capacity_at(&self, time: u64) -> Capacity {
+        self.machines
+            .keys()
+            .filter_map(|m| self.capacity_at(*m, time))
+            .fold(Capacity::default(), |acc, c| Capacity {
+                cpus: acc.cpus + c.cpus,
+                memory: acc.memory + c.memory,
+            })
+    }
+
+    /// The total capacity of the cluster over time, as a step function.
+    pub fn cluster_capacity(&self) -> Vec<(u64, Capacity)> {
+        // capacity deltas, and the change in the number of machines in the cluster
+        let mut deltas = Vec::new();
+        for steps in self.machines.values() {
+            let mut prev: Option<Capacity> = None;
+            for (t, c) in steps {
+                let (before, after) = (prev.unwrap_or_default(), c.unwrap_or_default());
+                let live = c.is_some() as i64 - prev.is_some() as i64;
+                deltas.push((
+                    *t,
+                    after.cpus - before.cpus,
+                    after.memory - before.memory,
+                    live,
+                ));
+                prev = *c;
+            }
+        }
+        deltas.sort_by_key(|(t, _, _, _)| *t);
+
+        let mut total = Capacity::default();
+        let mut live = 0;
+        let mut steps: Vec<(u64, Capacity)> = Vec::new();
+        for (t, cpus, memory, live_delta) in deltas {
+            total.cpus += cpus;
+            total.memory += memory;
+            live += live_delta;
+            if live == 0 {
+                // summing the deltas leaves rounding errors behind
+                total = Capacity::default();
+            }
+            match steps.last_mut() {
+                Some((last_t, c)) if *last_t == t => *c = total,
+                _ => steps.push((t, total)),
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse;
+
+    fn timeline(lines: &[&str]) -> MachineTimeline {
+        MachineTimeline::from_events(lines.iter().map(|l| Ok(parse(l)))).unwrap()
+    }
+
+    fn capacity(cpus: f64, memory: f64) -> Option<Capacity> {
+        Some(Capacity { cpus, memory })
+    }
+
+    #[test]
+    fn capacity_steps_start_at_their_events() {
+        let t = timeline(&["10,1,0,p1,0.5,0.5", "20,1,2,,0.25,"]);
+        assert_eq!(t.capacity_at(1, 9), None);
+        assert_eq!(t.capacity_at(1, 10), capacity(0.5, 0.5));
+        assert_eq!(t.capacity_at(1, 19), capacity(0.5, 0.5));
+        assert_eq!(t.capacity_at(1, 20), capacity(0.25, 0.5));
+        assert_eq!(t.capacity_at(2, 20), None);
+        assert!(t.machine_steps(2).is_empty());
+    }
+
+    #[test]
+    fn removed_machines_can_return() {
+        let t = timeline(&[
+            "0,1,0,p1,0.5,0.5",
+            "30,1,1,,,",
+            "40,1,0,p1,0.25,",
+            "50,1,1,,,",
+        ]);
+        assert_eq!(t.capacity_at(1, 29), capacity(0.5, 0.5));
+        assert_eq!(t.capacity_at(1, 30), None);
+        assert_eq!(t.capacity_at(1, 40), capacity(0.25, 0.0));
+        assert_eq!(t.capacity_at(1, 60), None);
+        assert_eq!(t.downtime(1), vec![(30, Some(40)), (50, None)]);
+        assert!(t.downtime(2).is_empty());
+    }
+
+    #[test]
+    fn events_at_one_timestamp_make_one_step() {
+        let t = timeline(&[
+            "0,1,0,p1,0.5,0.5",
+            "0,1,2,,,0.25",
+            "10,1,1,,,",
+            "10,1,0,p1,0.5,0.5",
+            "20,2,0,p1,0.25,0.25",
+        ]);
+        assert_eq!(
+            t.machine_steps(1),
+            &[(0, capacity(0.5, 0.25)), (10, capacity(0.5, 0.5))]
+        );
+        assert!(t.downtime(1).is_empty());
+        assert_eq!(
+            t.cluster_capacity_at(20),
+            Capacity {
+                cpus: 0.75,
+                memory: 0.75
+            }
+        );
+        assert_eq!(
+            t.cluster_capacity(),
+            vec![
+                (
+                    0,
+                    Capacity {
+                        cpus: 0.5,
+                        memory: 0.25
+                    }
+                ),
+                (
+                    10,
+                    Capacity {
+                        cpus: 0.5,
+                        memory: 0.5
+                    }
+                ),
+                (
+                    20,
+                    Capacity {
+                        cpus: 0.75,
+                        memory: 0.75
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn cluster_capacity_returns_to_zero() {
+        let t = timeline(&[
+            "0,1,0,p1,0.3,0.3",
+            "0,2,0,p1,0.6,0.6",
+            "0,3,0,p1,0.1,0.1",
+            "10,1,1,,,",
+            "20,2,1,,,",
+            "30,3,1,,,",
+            "40,2,0,p1,0.6,0.6",
+        ]);
+        let steps = t.cluster_capacity();
+        assert_eq!(steps.len(), 5);
+        assert_eq!(steps[3], (30, Capacity::default()));
+        assert_eq!(steps[4], (40, capacity(0.6, 0.6).unwrap()));
+        assert_eq!(t.cluster_capacity_at(35), Capacity::default());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::common::TraceRecord;
+use crate::error::Error;
+use crate::iter::TraceFileIterator;
+use crate::job_events::JobEvent;
+use crate::machine_attributes::MachineAttribute;
+use crate::machine_events::MachineEvent;
+use crate::task_events::TaskEvent;
+use crate::task_usage::TaskUsageRecord;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::Path;
+
+/// The tables that can be merged into a stream of `TraceEvent`s.
+///
+/// When records from several tables share a timestamp, they are ordered as the variants are
+/// declared here, so that machines exist before tasks are placed on them and jobs exist before
+/// their tasks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Table {
+    MachineEvents,
+    MachineAttributes,
+    JobEvents,
+    TaskEvents,
+    TaskUsage,
+}
+
+impl Table {
+    pub const ALL: [Table; 5] = [
+        Table::MachineEvents,
+        Table::MachineAttributes,
+        Table::JobEvents,
+        Table::TaskEvents,
+        Table::TaskUsage,
+    ];
+}
+
+/// A record from any of the trace's event and usage tables.
+#[derive(Clone, Debug)]
+pub enum TraceEvent {
+    Machine(MachineEvent),
+    MachineAttribute(MachineAttribute),
+    Job(JobEvent),
+    Task(TaskEvent),
+    TaskUsage(TaskUsageRecord),
+}
+
+impl TraceEvent {
+    /// The event's timestamp; for task usage records, this is the start of the measurement
+    /// period.
+    pub fn time(&self) -> u64 {
+        match self {
+            TraceEvent::Machine(e) => e.time(),
+            TraceEvent::MachineAttribute(e) => e.time(),
+            TraceEvent::Job(e) => e.time(),
+            TraceEvent::Task(e) => e.time(),
+            TraceEvent::TaskUsage(e) => e.time(),
+        }
+    }
+
+    /// The table the event was read from.
+    pub fn table(&self) -> Table {
+        match self {
+            TraceEvent::Machine(_) => Table::MachineEvents,
+            TraceEvent::MachineAttribute(_) => Table::MachineAttributes,
+            TraceEvent::Job(_) => Table::JobEvents,
+            TraceEvent::Task(_) => Table::TaskEvents,
+            TraceEvent::TaskUsage(_) => Table::TaskUsage,
+        }
+    }
+}
+
+type EventSource = Box<dyn Iterator<Item = Result<TraceEvent, Error>> + Send>;
+
+/// Merges the records of several tables into a single stream in timestamp order.
+///
+/// Records with equal timestamps are ordered by their table (see `Table`), and then by their
+/// order within the table.
+pub struct MergedEventIterator {
+    sources: Vec<(Table, EventSource)>,
+    heads: Vec<Option<TraceEvent>>,
+    heap: BinaryHeap<Reverse<(u64, Table, usize)>>,
+    refill: Vec<usize>,
+}
+
+impl MergedEventIterator {
+    /// Merges all records of the given tables.
+    pub fn new(trace_path: &str, tables: &[Table]) -> Result<Self, Error> {
+        Self::open(trace_path, tables, None)
+    }
+
+    /// Merges the records of the given tables with timestamps in `[start, end)`.
+    pub fn range(trace_path: &str, tables: &[Table], start: u64, end: u64) -> Result<Self, Error> {
+        Self::open(trace_path, tables, Some((start, end)))
+    }
+
+    fn open(trace_path: &str, tables: &[Table], range: Option<(u64, u64)>) -> Result<Self, Error> {
+        let mut tables = tables.to_vec();
+        tables.sort();
+        tables.dedup();
+        let mut sources = Vec::with_capacity(tables.len());
+        for table in tables {
+            let source = match table {
+                Table::MachineEvents => Self::source(trace_path, range, TraceEvent::Machine)?,
+                Table::MachineAttributes => {
+                    Self::source(trace_path, range, TraceEvent::MachineAttribute)?
+                }
+                Table::JobEvents => Self::source(trace_path, range, TraceEvent::Job)?,
+                Table::TaskEvents => Self::source(trace_path, range, TraceEvent::Task)?,
+                Table::TaskUsage => Self::source(trace_path, range, TraceEvent::TaskUsage)?,
+            };
+            sources.push((table, source));
+        }
+        Ok(Self::from_sources(sources))
+    }
+
+    /// Merges sources that each yield the records of one table, in time order.
+    fn from_sources(sources: Vec<(Table, EventSource)>) -> Self {
+        MergedEventIterator {
+            heads: sources.iter().map(|_| None).collect(),
+            heap: BinaryHeap::with_capacity(sources.len()),
+            refill: (0..sources.len()).collect(),
+            sources,
+        }
+    }
+
+    fn source<T>(
+        trace_path: &str,
+        range: Option<(u64, u64)>,
+        f: fn(T) -> TraceEvent,
+    ) -> Result<EventSource, Error>
+    where
+        T: TraceRecord + Send + 'static,
+    {
+        let dir = Path::new(trace_path).join(T::TABLE);
+        let iter = match range {
+            None => TraceFileIterator::new(&dir)?,
+            Some((start, end)) => TraceFileIterator::range(&dir, start, end)?,
+        };
+        Ok(Box::new(iter.map(move |r| r.map(f))))
+    }
+}
+
+impl Iterator for MergedEventIterator {
+    type Item = Result<TraceEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // pull the next record from every source whose previous head was consumed; on error, the
+        // source stays due for a refill so that the caller can skip the bad record and continue
+        while let Some(i) = self.refill.pop() {
+            let (table, source) = &mut self.sources[i];
+            match source.next() {
+                Some(Ok(ev)) => {
+                    self.heap.push(Reverse((ev.time(), *table, i)));
+                    self.heads[i] = Some(ev);
+                }
+                Some(Err(e)) => {
+                    self.refill.push(i);
+                    return Some(Err(e));
+                }
+                None => (),
+            }
+        }
+
+        let Reverse((_, _, i)) = self.heap.pop()?;
+        self.refill.push(i);
+        self.heads[i].take().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse;
+
+    fn source(events: Vec<Result<TraceEvent, Error>>) -> EventSource {
+        Box::new(events.into_iter())
+    }
+
+    fn machine_event(time: u64) -> Result<TraceEvent, Error> {
+        Ok(TraceEvent::Machine(parse(&format!(
+            "{},1,0,,0.5,0.5",
+            time
+        ))))
+    }
+
+    fn job_event(time: u64) -> Result<TraceEvent, Error> {
+        Ok(TraceEvent::Job(parse(&format!("{},,1,0,,,,", time))))
+    }
+
+    fn task_event(time: u64, task_index: u64) -> Result<TraceEvent, Error> {
+        let line = format!("{},,1,{},,0,,,0,,,,", time, task_index);
+        Ok(TraceEvent::Task(parse(&line)))
+    }
+
+    fn usage(time: u64) -> Result<TraceEvent, Error> {
+        let line = format!("{},{},1,0,1,,,,,,,,,,,,,,,", time, time + 300);
+        Ok(TraceEvent::TaskUsage(parse(&line)))
+    }
+
+    /// The events' tables, times and task indices, in order.
+    fn order(events: MergedEventIterator) -> Vec<(Table, u64, Option<u64>)> {
+        events
+            .map(|e| {
+                let e = e.unwrap();
+                let task_index = match &e {
+                    TraceEvent::Task(t) => Some(t.task_index),
+                    _ => None,
+                };
+                (e.table(), e.time(), task_index)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ties_are_broken_by_table_then_source_order() {
+        let events = MergedEventIterator::from_sources(vec![
+            (Table::MachineEvents, source(vec![machine_event(5)])),
+            (Table::JobEvents, source(vec![job_event(5), job_event(7)])),
+            (
+                Table::TaskEvents,
+                source(vec![task_event(3, 0), task_event(5, 2), task_event(5, 1)]),
+            ),
+            (Table::TaskUsage, source(vec![usage(5)])),
+        ]);
+        assert_eq!(
+            order(events),
+            vec![
+                (Table::TaskEvents, 3, Some(0)),
+                (Table::MachineEvents, 5, None),
+                (Table::JobEvents, 5, None),
+                (Table::TaskEvents, 5, Some(2)),
+                (Table::TaskEvents, 5, Some(1)),
+                (Table::TaskUsage, 5, None),
+                (Table::JobEvents, 7, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_do_not_end_the_stream() {
+        let bad = Err(Error::part_files(Path::new("task_events"), "bad".into()));
+        let mut events = MergedEventIterator::from_sources(vec![
+            (Table::JobEvents, source(vec![job_event(0), job_event(10)])),
+            (
+                Table::TaskEvents,
+                source(vec![task_event(0, 0), bad, task_event(5, 1)]),
+            ),
+        ]);
+        assert_eq!(events.next().unwrap().unwrap().table(), Table::JobEvents);
+        assert_eq!(events.next().unwrap().unwrap().table(), Table::TaskEvents);
+        assert!(events.next().unwrap().is_err());
+        assert_eq!(
+            order(events),
+            vec![
+                (Table::TaskEvents, 5, Some(1)),
+                (Table::JobEvents, 10, None)
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use crate::common::TraceRecord;
+use crate::error::Error;
+use crate::iter::{part_files, PartFileIterator};
+use rayon::prelude::*;
+use std::path::Path;
+
+/// Returns a parallel iterator over the part files of the table holding `T` records in the trace
+/// at `trace_path`. Each part file is opened on the worker thread that processes it.
+pub fn par_parts<T>(
+    trace_path: &str,
+) -> Result<impl IndexedParallelIterator<Item = Result<PartFileIterator<T>, Error>>, Error>
+where
+    T: TraceRecord + Send,
+{
+    let files = part_files(&Path::new(trace_path).join(T::TABLE))?;
+    Ok(files.into_par_iter().map(|p| PartFileIterator::open(&p)))
+}
+
+/// Calls `f` on each part file of the table holding `T` records in parallel, stopping at the
+/// first error.
+pub fn par_for_each_part<T, F>(trace_path: &str, f: F) -> Result<(), Error>
+where
+    T: TraceRecord + Send,
+    F: Fn(PartFileIterator<T>) -> Result<(), Error> + Sync + Send,
+{
+    par_parts(trace_path)?.try_for_each(|part| f(part?))
+}
+
+/// Computes a partial result from each part file of the table holding `T` records with `map`, in
+/// parallel, and merges the partial results with `reduce`. `identity` must return a value that
+/// leaves any other unchanged when reduced with it.
+pub fn par_map_reduce_parts<T, R, I, M, F>(
+    trace_path: &str,
+    identity: I,
+    map: M,
+    reduce: F,
+) -> Result<R, Error>
+where
+    T: TraceRecord + Send,
+    R: Send,
+    I: Fn() -> R + Sync + Send,
+    M: Fn(PartFileIterator<T>) -> Result<R, Error> + Sync + Send,
+    F: Fn(R, R) -> R + Sync + Send,
+{
+    par_parts(trace_path)?
+        .map(|part| map(part?))
+        .try_reduce(identity, |a, b| Ok(reduce(a, b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine_events::{MachineEvent, MachineEventIterator};
+    use crate::testing::{scratch_dir, write_part};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// A trace whose machine_events table has four parts, the third of which holds an invalid
+    /// record if `bad_record` is set.
+    fn fixture(bad_record: bool) -> String {
+        let trace = scratch_dir();
+        for part in 0..4 {
+            let mut rows: Vec<String> = (0..=part)
+                .map(|i| format!("{},{},0,,0.{},0.5", part * 10 + i, i, part + 1))
+                .collect();
+            if bad_record && part == 2 {
+                rows.push("30,9,7,,0.5,0.5".to_string());
+            }
+            let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+            let name = format!("part-{:05}-of-00004.csv", part);
+            write_part(&trace, "machine_events", &name, &rows);
+        }
+        trace.to_str().unwrap().to_string()
+    }
+
+    fn cpus(part: PartFileIterator<MachineEvent>) -> Result<f64, Error> {
+        let mut sum = 0.0;
+        for event in part {
+            sum += event?.cpus.unwrap_or(0.0);
+        }
+        Ok(sum)
+    }
+
+    #[test]
+    fn map_reduce_matches_sequential_result() {
+        let trace = fixture(false);
+        let parallel = par_map_reduce_parts(&trace, || 0.0, cpus, |a: f64, b: f64| a + b).unwrap();
+        let sequential: f64 = MachineEventIterator::new(&trace)
+            .unwrap()
+            .map(|e| e.unwrap().cpus.unwrap())
+            .sum();
+        assert!((parallel - sequential).abs() < 1e-9);
+        assert!((parallel - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn for_each_part_visits_every_part() {
+        let trace = fixture(false);
+        let records = AtomicU64::new(0);
+        par_for_each_part::<MachineEvent, _>(&trace, |part| {
+            let n = part.count() as u64;
+            records.fetch_add(n, Ordering::Relaxed);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(records.into_inner(), 10);
+    }
+
+    #[test]
+    fn errors_in_a_part_are_returned() {
+        let trace = fixture(true);
+        match par_map_reduce_parts(&trace, || 0.0, cpus, |a, b| a + b) {
+            Err(Error::Schema { path, line, .. }) => {
+                assert!(path.ends_with("part-00002-of-00004.csv"));
+                assert_eq!(line, Some(4));
+            }
+            other => panic!("expected a schema error, got {:?}", other),
+        }
+        let result = par_for_each_part::<MachineEvent, _>(&trace, |mut part| {
+            part.try_for_each(|e| e.map(|_| ()))
+        });
+        assert!(result.is_err());
+    }
+}
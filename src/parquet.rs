@@ -0,0 +1,398 @@
+use crate::common::TraceRecord;
+use crate::error::Error;
+use crate::schema::{self, Column, ColumnType, Value};
+use ::parquet::basic::{Compression, LogicalType, Repetition, Type as PhysicalType};
+use ::parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type};
+use ::parquet::errors::ParquetError;
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::writer::SerializedFileWriter;
+use ::parquet::schema::types::{Type, TypePtr};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The span of trace time covered by a row group unless configured otherwise (one hour).
+pub static DEFAULT_ROW_GROUP_DURATION: u64 = 3_600_000_000;
+
+/// The most rows in a row group; a time range with more records is split over several groups.
+pub static MAX_ROW_GROUP_ROWS: usize = 1 << 20;
+
+/// The Parquet file for a table in `out_dir`.
+pub fn parquet_path<T: TraceRecord>(out_dir: &str) -> PathBuf {
+    Path::new(out_dir).join(format!("{}.parquet", T::TABLE))
+}
+
+/// The Parquet schema of a table, derived from its record struct: enums and other `u8` fields
+/// become 8-bit unsigned integers, and `Option` fields become nullable columns.
+pub fn parquet_schema<T: TraceRecord>() -> Result<TypePtr, ParquetError> {
+    let columns = schema::columns::<T>().map_err(|e| ParquetError::General(e.to_string()))?;
+    let fields = columns
+        .iter()
+        .map(|c| {
+            let (physical, logical) = match c.column_type {
+                ColumnType::Bool => (PhysicalType::BOOLEAN, None),
+                ColumnType::U8 => (PhysicalType::INT32, Some(unsigned(8))),
+                ColumnType::U64 => (PhysicalType::INT64, Some(unsigned(64))),
+                ColumnType::I64 => (PhysicalType::INT64, None),
+                ColumnType::F64 => (PhysicalType::DOUBLE, None),
+                ColumnType::Str => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+            };
+            let repetition = if c.nullable {
+                Repetition::OPTIONAL
+            } else {
+                Repetition::REQUIRED
+            };
+            Type::primitive_type_builder(c.name, physical)
+                .with_repetition(repetition)
+                .with_logical_type(logical)
+                .build()
+                .map(Arc::new)
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Arc::new(
+        Type::group_type_builder(T::TABLE)
+            .with_fields(fields)
+            .build()?,
+    ))
+}
+
+fn unsigned(bit_width: i8) -> LogicalType {
+    LogicalType::Integer {
+        bit_width,
+        is_signed: false,
+    }
+}
+
+/// Writes all `records` of a table to its Parquet file in `out_dir`, with one row group per hour
+/// of trace time. Returns the number of records written.
+pub fn write_parquet<T, I>(out_dir: &str, records: I) -> Result<u64, Error>
+where
+    T: TraceRecord + Serialize,
+    I: IntoIterator<Item = Result<T, Error>>,
+{
+    let mut writer = ParquetWriter::create(out_dir)?;
+    for r in records {
+        writer.write(&r?)?;
+    }
+    writer.finish()
+}
+
+enum Values {
+    Bool(Vec<bool>),
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+    Bytes(Vec<ByteArray>),
+}
+
+/// The buffered values of one column of the pending row group.
+struct ColumnBuffer {
+    values: Values,
+    /// The definition level of each row (0 for null, 1 otherwise), for nullable columns.
+    def_levels: Option<Vec<i16>>,
+}
+
+impl ColumnBuffer {
+    fn new(column: &Column) -> Self {
+        let values = match column.column_type {
+            ColumnType::Bool => Values::Bool(Vec::new()),
+            ColumnType::U8 => Values::Int32(Vec::new()),
+            ColumnType::U64 | ColumnType::I64 => Values::Int64(Vec::new()),
+            ColumnType::F64 => Values::Double(Vec::new()),
+            ColumnType::Str => Values::Bytes(Vec::new()),
+        };
+        ColumnBuffer {
+            values,
+            def_levels: if column.nullable {
+                Some(Vec::new())
+            } else {
+                None
+            },
+        }
+    }
+
+    fn push(&mut self, value: Value) -> Result<(), String> {
+        if let Some(levels) = &mut self.def_levels {
+            levels.push((value != Value::Null) as i16);
+        }
+        match (&mut self.values, value) {
+            (_, Value::Null) if self.def_levels.is_some() => {}
+            (Values::Bool(v), Value::Bool(b)) => v.push(b),
+            (Values::Int32(v), Value::UInt(u)) => v.push(u as i32),
+            (Values::Int64(v), Value::UInt(u)) => v.push(u as i64),
+            (Values::Int64(v), Value::Int(i)) => v.push(i),
+            (Values::Double(v), Value::Float(f)) => v.push(f),
+            (Values::Bytes(v), Value::Str(s)) => v.push(ByteArray::from(s.into_bytes())),
+            (_, value) => return Err(format!("unexpected value {:?}", value)),
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        match &mut self.values {
+            Values::Bool(v) => v.clear(),
+            Values::Int32(v) => v.clear(),
+            Values::Int64(v) => v.clear(),
+            Values::Double(v) => v.clear(),
+            Values::Bytes(v) => v.clear(),
+        }
+        if let Some(levels) = &mut self.def_levels {
+            levels.clear();
+        }
+    }
+}
+
+/// Writes a table's records to a Parquet file, one row group per span of trace time.
+///
+/// Row groups never straddle a multiple of the row group duration, so readers that prune row
+/// groups by their `time` statistics read little beyond the range they ask for. Records are
+/// expected in time order, as the table iterators yield them.
+pub struct ParquetWriter<T> {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    writer: SerializedFileWriter<BufWriter<File>>,
+    columns: Vec<Column>,
+    buffers: Vec<ColumnBuffer>,
+    rows: usize,
+    row_group_duration: u64,
+    /// The time range (as a multiple of the row group duration) of the pending row group.
+    range: Option<u64>,
+    written: u64,
+    _record: PhantomData<T>,
+}
+
+impl<T: TraceRecord + Serialize> ParquetWriter<T> {
+    /// Creates the Parquet file for `T`'s table in `out_dir`, creating the directory if needed.
+    pub fn create(out_dir: &str) -> Result<Self, Error> {
+        Self::with_row_group_duration(out_dir, DEFAULT_ROW_GROUP_DURATION)
+    }
+
+    /// Like `create`, but with row groups covering `duration` microseconds of trace time.
+    pub fn with_row_group_duration(out_dir: &str, duration: u64) -> Result<Self, Error> {
+        assert!(duration > 0, "row groups must cover some trace time");
+        let path = parquet_path::<T>(out_dir);
+        let tmp_path = path.with_extension("parquet.tmp");
+        fs::create_dir_all(out_dir).map_err(|e| Error::io(Path::new(out_dir), e))?;
+        let columns = schema::columns::<T>().map_err(|e| Error::export(&path, e.to_string()))?;
+        let schema = parquet_schema::<T>().map_err(|e| Error::export(&path, e.to_string()))?;
+        let file = File::create(&tmp_path).map_err(|e| Error::io(&tmp_path, e))?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(MAX_ROW_GROUP_ROWS)
+            .build();
+        let writer = SerializedFileWriter::new(BufWriter::new(file), schema, Arc::new(properties))
+            .map_err(|e| Error::export(&tmp_path, e.to_string()))?;
+        Ok(ParquetWriter {
+            path,
+            tmp_path,
+            writer,
+            buffers: columns.iter().map(ColumnBuffer::new).collect(),
+            columns,
+            rows: 0,
+            row_group_duration: duration,
+            range: None,
+            written: 0,
+            _record: PhantomData,
+        })
+    }
+
+    pub fn write(&mut self, record: &T) -> Result<(), Error> {
+        let range = record.time() / self.row_group_duration;
+        if self.range != Some(range) || self.rows == MAX_ROW_GROUP_ROWS {
+            self.flush_row_group()?;
+            self.range = Some(range);
+        }
+        let row = schema::to_row(record).map_err(|e| Error::export(&self.path, e.to_string()))?;
+        let path = &self.path;
+        for ((value, buffer), column) in row.into_iter().zip(&mut self.buffers).zip(&self.columns) {
+            buffer
+                .push(value)
+                .map_err(|e| Error::export(path, format!("column `{}`: {}", column.name, e)))?;
+        }
+        self.rows += 1;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn flush_row_group(&mut self) -> Result<(), Error> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let tmp_path = &self.tmp_path;
+        let export = |e: ParquetError| Error::export(tmp_path, e.to_string());
+        let mut row_group = self.writer.next_row_group().map_err(export)?;
+        for buffer in &self.buffers {
+            let mut column = match row_group.next_column().map_err(export)? {
+                Some(c) => c,
+                None => break,
+            };
+            let levels = buffer.def_levels.as_deref();
+            match &buffer.values {
+                Values::Bool(v) => column.typed::<BoolType>().write_batch(v, levels, None),
+                Values::Int32(v) => column.typed::<Int32Type>().write_batch(v, levels, None),
+                Values::Int64(v) => column.typed::<Int64Type>().write_batch(v, levels, None),
+                Values::Double(v) => column.typed::<DoubleType>().write_batch(v, levels, None),
+                Values::Bytes(v) => column.typed::<ByteArrayType>().write_batch(v, levels, None),
+            }
+            .map_err(export)?;
+            column.close().map_err(export)?;
+        }
+        row_group.close().map_err(export)?;
+        self.buffers.iter_mut().for_each(ColumnBuffer::clear);
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the file footer, and moves the file into place. Returns the
+    /// number of records written.
+    pub fn finish(mut self) -> Result<u64, Error> {
+        self.flush_row_group()?;
+        let ParquetWriter {
+            path,
+            tmp_path,
+            writer,
+            written,
+            ..
+        } = self;
+        writer
+            .close()
+            .map_err(|e| Error::export(&tmp_path, e.to_string()))?;
+        fs::rename(&tmp_path, &path).map_err(|e| Error::io(&path, e))?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine_events::{MachineEvent, MachineEventType};
+    use crate::testing::scratch_dir;
+    use ::parquet::file::reader::{FileReader, SerializedFileReader};
+    use ::parquet::record::Field;
+
+    fn machine_event(time: u64, platform_id: Option<&str>, cpus: Option<f64>) -> MachineEvent {
+        MachineEvent {
+            time,
+            machine_id: 5,
+            event_type: MachineEventType::Update,
+            platform_id: platform_id.map(str::to_string),
+            cpus,
+            memory: Some(0.25),
+        }
+    }
+
+    #[test]
+    fn machine_events_round_trip() {
+        let dir = scratch_dir();
+        let dir = dir.to_str().unwrap();
+        let records = vec![
+            machine_event(0, Some("plat1"), Some(0.5)),
+            machine_event(50, None, None),
+            machine_event(99, Some("plat0"), Some(1.0)),
+            machine_event(100, None, Some(0.5)),
+            machine_event(250, Some("plat1"), None),
+            machine_event(260, Some("plat1"), Some(0.5)),
+        ];
+        let mut writer = ParquetWriter::with_row_group_duration(dir, 100).unwrap();
+        for r in &records {
+            writer.write(r).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), 6);
+
+        let file = File::open(parquet_path::<MachineEvent>(dir)).unwrap();
+        let reader = SerializedFileReader::new(file).unwrap();
+        let metadata = reader.metadata();
+
+        let schema = metadata.file_metadata().schema_descr();
+        let types: Vec<(String, PhysicalType, Option<LogicalType>, Repetition)> = (0..schema
+            .num_columns())
+            .map(|i| {
+                let c = schema.column(i);
+                let t = c.self_type();
+                let info = t.get_basic_info();
+                (
+                    c.name().to_string(),
+                    c.physical_type(),
+                    info.logical_type(),
+                    info.repetition(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                (
+                    "time".to_string(),
+                    PhysicalType::INT64,
+                    Some(unsigned(64)),
+                    Repetition::REQUIRED
+                ),
+                (
+                    "machine_id".to_string(),
+                    PhysicalType::INT64,
+                    Some(unsigned(64)),
+                    Repetition::REQUIRED
+                ),
+                (
+                    "event_type".to_string(),
+                    PhysicalType::INT32,
+                    Some(unsigned(8)),
+                    Repetition::REQUIRED
+                ),
+                (
+                    "platform_id".to_string(),
+                    PhysicalType::BYTE_ARRAY,
+                    Some(LogicalType::String),
+                    Repetition::OPTIONAL
+                ),
+                (
+                    "cpus".to_string(),
+                    PhysicalType::DOUBLE,
+                    None,
+                    Repetition::OPTIONAL
+                ),
+                (
+                    "memory".to_string(),
+                    PhysicalType::DOUBLE,
+                    None,
+                    Repetition::OPTIONAL
+                ),
+            ]
+        );
+
+        // row groups break at multiples of the duration
+        let group_rows: Vec<i64> = metadata.row_groups().iter().map(|g| g.num_rows()).collect();
+        assert_eq!(group_rows, vec![3, 1, 2]);
+
+        let rows: Vec<Vec<Field>> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .map(|(_, f)| f.clone())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(rows.len(), records.len());
+        assert_eq!(
+            rows[1],
+            vec![
+                Field::ULong(50),
+                Field::ULong(5),
+                Field::UByte(2),
+                Field::Null,
+                Field::Null,
+                Field::Double(0.25),
+            ]
+        );
+        assert_eq!(rows[4][3], Field::Str("plat1".to_string()));
+        assert_eq!(rows[4][4], Field::Null);
+        let times: Vec<Field> = rows.iter().map(|r| r[0].clone()).collect();
+        let expected: Vec<Field> = records.iter().map(|r| Field::ULong(r.time)).collect();
+        assert_eq!(times, expected);
+    }
+}
@@ -0,0 +1,342 @@
+use crate::common::{AFTER_TRACE_TIME, TRACE_START_TIME};
+use crate::distribution::Distribution;
+use crate::error::Error;
+use crate::merge::{MergedEventIterator, Table, TraceEvent};
+use std::iter::Peekable;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+struct Control {
+    paused: bool,
+    stopped: bool,
+    seek: Option<u64>,
+    position: u64,
+}
+
+/// Controls a running `Replay` from other threads.
+#[derive(Clone)]
+pub struct ReplayHandle {
+    control: Arc<(Mutex<Control>, Condvar)>,
+}
+
+impl ReplayHandle {
+    fn lock(&self) -> MutexGuard<'_, Control> {
+        self.control.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update<F: FnOnce(&mut Control)>(&self, f: F) {
+        f(&mut self.lock());
+        self.control.1.notify_all();
+    }
+
+    /// Stops emitting events until `resume` is called. Time spent paused does not count towards
+    /// the replay's schedule.
+    pub fn pause(&self) {
+        self.update(|c| c.paused = true)
+    }
+
+    pub fn resume(&self) {
+        self.update(|c| c.paused = false)
+    }
+
+    /// Continues the replay from the first event at or after trace time `time`, which may be
+    /// before or after the current position. The schedule restarts from `time` (or from
+    /// `TRACE_START_TIME`, if earlier) at the moment of the seek.
+    pub fn seek(&self, time: u64) {
+        self.update(|c| c.seek = Some(time))
+    }
+
+    /// Ends the replay after the event being emitted, if any.
+    pub fn stop(&self) {
+        self.update(|c| c.stopped = true)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    /// The trace time of the last event emitted.
+    pub fn position(&self) -> u64 {
+        self.lock().position
+    }
+}
+
+/// How closely a replay kept to its schedule.
+#[derive(Clone, Debug)]
+pub struct ReplayStats {
+    pub events: u64,
+    /// How late events were emitted relative to their scheduled wall-clock time, in milliseconds.
+    /// Events from before `TRACE_START_TIME` (the initial cluster state) and after the trace
+    /// window are emitted immediately and not included.
+    pub drift: Distribution,
+    pub max_drift: Duration,
+    pub elapsed: Duration,
+}
+
+/// Replays the merged event stream of a trace in scaled real time.
+///
+/// An event at trace time `t` is emitted `(t - TRACE_START_TIME) / speedup` microseconds after the
+/// replay starts; the events of the initial cluster state, before `TRACE_START_TIME`, are emitted
+/// straight away, and those after the trace window, at `AFTER_TRACE_TIME`, straight after the
+/// last event within it. Events are never emitted early, but are emitted late if the consumer or
+/// the trace reader cannot keep up.
+pub struct Replay {
+    trace_path: String,
+    tables: Vec<Table>,
+    speedup: f64,
+    events: Peekable<MergedEventIterator>,
+    handle: ReplayHandle,
+}
+
+impl Replay {
+    /// Prepares a replay of the given tables of the trace at `trace_path`, running `speedup` times
+    /// faster than the trace's own time.
+    pub fn new(trace_path: &str, tables: &[Table], speedup: f64) -> Result<Self, Error> {
+        assert!(speedup > 0.0, "speedup must be positive");
+        Ok(Replay {
+            trace_path: trace_path.to_string(),
+            tables: tables.to_vec(),
+            speedup,
+            events: MergedEventIterator::new(trace_path, tables)?.peekable(),
+            handle: ReplayHandle {
+                control: Arc::new((
+                    Mutex::new(Control {
+                        paused: false,
+                        stopped: false,
+                        seek: None,
+                        position: 0,
+                    }),
+                    Condvar::new(),
+                )),
+            },
+        })
+    }
+
+    /// A handle to pause, seek or stop the replay while it runs.
+    pub fn handle(&self) -> ReplayHandle {
+        self.handle.clone()
+    }
+
+    /// Runs the replay, passing each event to `f` when it is due. Blocks until the events are
+    /// exhausted or the replay is stopped.
+    pub fn run<F: FnMut(TraceEvent)>(self, mut f: F) -> Result<ReplayStats, Error> {
+        self.drive(|e| {
+            f(e);
+            true
+        })
+    }
+
+    /// Runs the replay, sending each event on `sender` when it is due. The replay ends early if the
+    /// receiver hangs up.
+    pub fn send_to(self, sender: Sender<TraceEvent>) -> Result<ReplayStats, Error> {
+        self.drive(|e| sender.send(e).is_ok())
+    }
+
+    /// Runs the replay's schedule without emitting any events, to measure how well reading the
+    /// trace keeps up at the chosen speedup.
+    pub fn dry_run(self) -> Result<ReplayStats, Error> {
+        self.drive(|_| true)
+    }
+
+    fn due(&self, anchor: (Instant, u64), time: u64) -> Instant {
+        if time >= AFTER_TRACE_TIME {
+            return anchor.0;
+        }
+        let offset = time.saturating_sub(anchor.1) as f64 / self.speedup;
+        anchor.0 + Duration::from_micros(offset as u64)
+    }
+
+    fn drive<F: FnMut(TraceEvent) -> bool>(mut self, mut emit: F) -> Result<ReplayStats, Error> {
+        let start = Instant::now();
+        let mut stats = ReplayStats {
+            events: 0,
+            drift: Distribution::new(3, 2),
+            max_drift: Duration::default(),
+            elapsed: Duration::default(),
+        };
+        // the wall-clock and trace times that the schedule is relative to
+        let mut anchor = (start, TRACE_START_TIME);
+        let mut paused_at: Option<Instant> = None;
+        let handle = self.handle.clone();
+        let cond = &handle.control.1;
+        loop {
+            let next = match self.events.peek() {
+                Some(Ok(e)) => Some(e.time()),
+                Some(Err(_)) => return Err(self.events.next().unwrap().unwrap_err()),
+                None => None,
+            };
+            let now = Instant::now();
+            let mut control = handle.lock();
+            if control.stopped {
+                break;
+            }
+            if let Some(time) = control.seek.take() {
+                drop(control);
+                self.events =
+                    MergedEventIterator::range(&self.trace_path, &self.tables, time, u64::MAX)?
+                        .peekable();
+                anchor = (now, time.max(TRACE_START_TIME));
+                paused_at = paused_at.map(|_| now);
+                continue;
+            }
+            if control.paused {
+                paused_at.get_or_insert(now);
+                drop(cond.wait(control));
+                continue;
+            }
+            if let Some(p) = paused_at.take() {
+                anchor.0 += now - p;
+                continue;
+            }
+            let time = match next {
+                Some(time) => time,
+                None => break,
+            };
+            let due = self.due(anchor, time);
+            if now < due {
+                // woken early by any control change
+                drop(cond.wait_timeout(control, due - now));
+                continue;
+            }
+            control.position = time;
+            drop(control);
+
+            let event = self.events.next().unwrap()?;
+            if time >= anchor.1 && time < AFTER_TRACE_TIME {
+                let drift = now - due;
+                stats.drift.record(drift.as_secs_f64() * 1000.0);
+                stats.max_drift = stats.max_drift.max(drift);
+            }
+            stats.events += 1;
+            if !emit(event) {
+                break;
+            }
+        }
+        stats.elapsed = start.elapsed();
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{scratch_dir, write_part};
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    const SECOND: u64 = 1_000_000;
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// A trace with a job event at each of `times`.
+    fn fixture(times: &[u64]) -> String {
+        let trace = scratch_dir();
+        let rows: Vec<String> = times
+            .iter()
+            .enumerate()
+            .map(|(i, t)| format!("{},,{},0,u1,1,j1,l1", t, i))
+            .collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        write_part(&trace, "job_events", "part-00000-of-00001.csv", &rows);
+        trace.to_str().unwrap().to_string()
+    }
+
+    fn replay(times: &[u64], speedup: f64) -> Replay {
+        Replay::new(&fixture(times), &[Table::JobEvents], speedup).unwrap()
+    }
+
+    /// Sends the replay's events on a channel from another thread.
+    fn spawn(
+        replay: Replay,
+    ) -> (
+        Receiver<TraceEvent>,
+        thread::JoinHandle<Result<ReplayStats, Error>>,
+    ) {
+        let (sender, receiver) = mpsc::channel();
+        (receiver, thread::spawn(move || replay.send_to(sender)))
+    }
+
+    fn next_time(events: &Receiver<TraceEvent>) -> u64 {
+        events.recv_timeout(TIMEOUT).unwrap().time()
+    }
+
+    #[test]
+    fn events_are_emitted_on_schedule() {
+        let start = TRACE_START_TIME;
+        let times = [0, start, start + 10 * SECOND, start + 20 * SECOND];
+        let mut emitted = Vec::new();
+        let stats = replay(&times, 1000.0)
+            .run(|e| emitted.push(e.time()))
+            .unwrap();
+        assert_eq!(emitted, times);
+        assert_eq!(stats.events, 4);
+        // the initial state's event is not scheduled
+        assert_eq!(stats.drift.len(), 3);
+        assert!(stats.elapsed >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn events_after_the_trace_follow_the_last_event() {
+        let times = [TRACE_START_TIME, AFTER_TRACE_TIME];
+        let (done, result) = mpsc::channel();
+        let r = replay(&times, 1000.0);
+        thread::spawn(move || done.send(r.dry_run().map(|s| (s.events, s.drift.len()))));
+        let stats = result.recv_timeout(TIMEOUT).unwrap().unwrap();
+        assert_eq!(stats, (2, 1));
+    }
+
+    #[test]
+    fn paused_time_does_not_count() {
+        let times = [TRACE_START_TIME, TRACE_START_TIME + 100 * SECOND];
+        let r = replay(&times, 1000.0);
+        let handle = r.handle();
+        let (events, thread) = spawn(r);
+        assert_eq!(next_time(&events), TRACE_START_TIME);
+        handle.pause();
+        assert!(handle.is_paused());
+        thread::sleep(Duration::from_millis(250));
+        assert!(events.try_recv().is_err());
+        handle.resume();
+        assert_eq!(next_time(&events), times[1]);
+        let stats = thread.join().unwrap().unwrap();
+        assert_eq!(stats.events, 2);
+        // the second event was due 100ms after the start, plus the time spent paused
+        assert!(stats.elapsed >= Duration::from_millis(350));
+        assert!(stats.max_drift < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn seek_and_stop() {
+        let start = TRACE_START_TIME;
+        let times = [0, start, start + 10_000 * SECOND, start + 20_000 * SECOND];
+        let r = replay(&times, 1.0);
+        let handle = r.handle();
+        let (events, thread) = spawn(r);
+        assert_eq!(next_time(&events), 0);
+        assert_eq!(next_time(&events), start);
+
+        // forwards, emitting the event sought to straight away
+        handle.seek(times[2]);
+        assert_eq!(next_time(&events), times[2]);
+        assert_eq!(handle.position(), times[2]);
+
+        // and back to the start of the trace
+        handle.seek(0);
+        assert_eq!(next_time(&events), 0);
+        assert_eq!(next_time(&events), start);
+
+        handle.stop();
+        let stats = thread.join().unwrap().unwrap();
+        assert_eq!(stats.events, 5);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn hanging_up_ends_the_replay() {
+        let times = [TRACE_START_TIME, TRACE_START_TIME + SECOND];
+        let (sender, receiver) = mpsc::channel();
+        drop(receiver);
+        let stats = replay(&times, 1e6).send_to(sender).unwrap();
+        assert_eq!(stats.events, 1);
+    }
+}
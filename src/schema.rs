@@ -0,0 +1,784 @@
+use serde::de::{self, DeserializeOwned, DeserializeSeed, SeqAccess, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt;
+
+/// The type of a column, as determined by the record struct's field type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColumnType {
+    Bool,
+    /// A `u8` field, including enums encoded as small integers.
+    U8,
+    U64,
+    I64,
+    F64,
+    Str,
+}
+
+/// A column of a table, corresponding to a field of its record struct.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub column_type: ColumnType,
+    /// Whether the field is an `Option`.
+    pub nullable: bool,
+}
+
+/// A single field of a record.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Value {
+    pub(crate) fn as_ref(&self) -> ValueRef<'_> {
+        match self {
+            Value::Null => ValueRef::Null,
+            Value::Bool(b) => ValueRef::Bool(*b),
+            Value::UInt(u) => ValueRef::UInt(*u),
+            Value::Int(i) => ValueRef::Int(*i),
+            Value::Float(f) => ValueRef::Float(*f),
+            Value::Str(s) => ValueRef::Str(s),
+        }
+    }
+}
+
+/// A borrowed `Value`, from which records can be built without copying strings twice.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ValueRef<'a> {
+    Null,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Str(&'a str),
+}
+
+/// A record that does not fit the row model (e.g., a field of an unsupported type), or a row
+/// that does not match the record struct.
+#[derive(Clone, Debug)]
+pub struct RowError(String);
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RowError {}
+
+impl de::Error for RowError {
+    fn custom<M: fmt::Display>(msg: M) -> Self {
+        RowError(msg.to_string())
+    }
+}
+
+impl ser::Error for RowError {
+    fn custom<M: fmt::Display>(msg: M) -> Self {
+        RowError(msg.to_string())
+    }
+}
+
+/// The columns of a record struct, in field order, derived from its `Deserialize` implementation.
+pub fn columns<T: DeserializeOwned>() -> Result<Vec<Column>, RowError> {
+    let mut columns = Vec::new();
+    T::deserialize(SchemaTracer {
+        columns: &mut columns,
+    })?;
+    Ok(columns)
+}
+
+/// Converts a record into its fields' values, in field order.
+pub fn to_row<T: Serialize>(record: &T) -> Result<Vec<Value>, RowError> {
+    record.serialize(RowSerializer)
+}
+
+/// Builds a record from its fields' values, in field order.
+pub fn from_row<T: DeserializeOwned>(row: &[Value]) -> Result<T, RowError> {
+    from_values(row.iter().map(Value::as_ref))
+}
+
+pub(crate) fn from_values<'a, T, I>(values: I) -> Result<T, RowError>
+where
+    T: DeserializeOwned,
+    I: Iterator<Item = ValueRef<'a>>,
+{
+    T::deserialize(RowDeserializer { values })
+}
+
+// schema tracing: the record is "deserialized" from a deserializer that notes which type each
+// field asks for and hands it a default value (0 for integers, so enums need a 0 variant)
+
+struct SchemaTracer<'a> {
+    columns: &'a mut Vec<Column>,
+}
+
+impl<'de, 'a> de::Deserializer<'de> for SchemaTracer<'a> {
+    type Error = RowError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, RowError> {
+        Err(de::Error::custom("records must be structs"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RowError> {
+        visitor.visit_seq(FieldTracer {
+            columns: self.columns,
+            fields,
+            next: 0,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+struct FieldTracer<'a> {
+    columns: &'a mut Vec<Column>,
+    fields: &'static [&'static str],
+    next: usize,
+}
+
+impl<'de, 'a> SeqAccess<'de> for FieldTracer<'a> {
+    type Error = RowError;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, RowError> {
+        let name = match self.fields.get(self.next) {
+            Some(name) => *name,
+            None => return Ok(None),
+        };
+        self.next += 1;
+        let mut field = TypeTracer {
+            column_type: None,
+            nullable: false,
+        };
+        let value = seed.deserialize(&mut field)?;
+        let column_type = field
+            .column_type
+            .ok_or_else(|| RowError(format!("field `{}` has an unsupported type", name)))?;
+        self.columns.push(Column {
+            name,
+            column_type,
+            nullable: field.nullable,
+        });
+        Ok(Some(value))
+    }
+}
+
+struct TypeTracer {
+    column_type: Option<ColumnType>,
+    nullable: bool,
+}
+
+impl TypeTracer {
+    fn unsupported<T>(&self) -> Result<T, RowError> {
+        Err(de::Error::custom("unsupported field type"))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut TypeTracer {
+    type Error = RowError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, RowError> {
+        self.unsupported()
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.column_type = Some(ColumnType::Bool);
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.column_type = Some(ColumnType::U8);
+        visitor.visit_u8(0)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.column_type = Some(ColumnType::U64);
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.column_type = Some(ColumnType::I64);
+        visitor.visit_i64(0)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.column_type = Some(ColumnType::F64);
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.column_type = Some(ColumnType::Str);
+        visitor.visit_str("")
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        self.nullable = true;
+        visitor.visit_some(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct map
+        struct enum identifier ignored_any
+    }
+}
+
+// records to rows
+
+struct RowSerializer;
+
+struct RowFields(Vec<Value>);
+
+impl ser::SerializeStruct for RowFields {
+    type Ok = Vec<Value>;
+    type Error = RowError;
+
+    fn serialize_field<F: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &F,
+    ) -> Result<(), RowError> {
+        self.0.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Vec<Value>, RowError> {
+        Ok(self.0)
+    }
+}
+
+macro_rules! not_a_struct {
+    ($($method:ident($($arg:ty),*);)*) => {
+        $(fn $method(self, $(_: $arg),*) -> Result<Vec<Value>, RowError> {
+            Err(ser::Error::custom("records must be structs"))
+        })*
+    };
+}
+
+impl ser::Serializer for RowSerializer {
+    type Ok = Vec<Value>;
+    type Error = RowError;
+    type SerializeSeq = Impossible<Vec<Value>, RowError>;
+    type SerializeTuple = Impossible<Vec<Value>, RowError>;
+    type SerializeTupleStruct = Impossible<Vec<Value>, RowError>;
+    type SerializeTupleVariant = Impossible<Vec<Value>, RowError>;
+    type SerializeMap = Impossible<Vec<Value>, RowError>;
+    type SerializeStruct = RowFields;
+    type SerializeStructVariant = Impossible<Vec<Value>, RowError>;
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<RowFields, RowError> {
+        Ok(RowFields(Vec::with_capacity(len)))
+    }
+
+    not_a_struct! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<V: Serialize + ?Sized>(self, _value: &V) -> Result<Vec<Value>, RowError> {
+        Err(ser::Error::custom("records must be structs"))
+    }
+
+    fn serialize_newtype_struct<V: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &V,
+    ) -> Result<Vec<Value>, RowError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<V: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &V,
+    ) -> Result<Vec<Value>, RowError> {
+        Err(ser::Error::custom("records must be structs"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, RowError> {
+        Err(ser::Error::custom("records must be structs"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, RowError> {
+        Err(ser::Error::custom("records must be structs"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, RowError> {
+        Err(ser::Error::custom("records must be structs"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, RowError> {
+        Err(ser::Error::custom("records must be structs"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, RowError> {
+        Err(ser::Error::custom("records must be structs"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, RowError> {
+        Err(ser::Error::custom("records must be structs"))
+    }
+}
+
+struct ValueSerializer;
+
+impl ValueSerializer {
+    fn unsupported(&self) -> RowError {
+        ser::Error::custom("unsupported field type")
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = RowError;
+    type SerializeSeq = Impossible<Value, RowError>;
+    type SerializeTuple = Impossible<Value, RowError>;
+    type SerializeTupleStruct = Impossible<Value, RowError>;
+    type SerializeTupleVariant = Impossible<Value, RowError>;
+    type SerializeMap = Impossible<Value, RowError>;
+    type SerializeStruct = Impossible<Value, RowError>;
+    type SerializeStructVariant = Impossible<Value, RowError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, RowError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, RowError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, RowError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, RowError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, RowError> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, RowError> {
+        Ok(Value::UInt(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, RowError> {
+        Ok(Value::UInt(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, RowError> {
+        Ok(Value::UInt(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, RowError> {
+        Ok(Value::UInt(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, RowError> {
+        Ok(Value::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, RowError> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, RowError> {
+        Ok(Value::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, RowError> {
+        Ok(Value::Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Value, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_none(self) -> Result<Value, RowError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<V: Serialize + ?Sized>(self, value: &V) -> Result<Value, RowError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, RowError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, RowError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<Value, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_newtype_struct<V: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &V,
+    ) -> Result<Value, RowError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<V: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &V,
+    ) -> Result<Value, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, RowError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, RowError> {
+        Err(self.unsupported())
+    }
+}
+
+// rows to records
+
+struct RowDeserializer<I> {
+    values: I,
+}
+
+impl<'de, 'a, I> de::Deserializer<'de> for RowDeserializer<I>
+where
+    I: Iterator<Item = ValueRef<'a>>,
+{
+    type Error = RowError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, RowError> {
+        Err(de::Error::custom("records must be structs"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RowError> {
+        visitor.visit_seq(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+impl<'de, 'a, I> SeqAccess<'de> for RowDeserializer<I>
+where
+    I: Iterator<Item = ValueRef<'a>>,
+{
+    type Error = RowError;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, RowError> {
+        match self.values.next() {
+            Some(v) => seed.deserialize(ValueDeserializer(v)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct ValueDeserializer<'a>(ValueRef<'a>);
+
+impl<'de, 'a> de::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = RowError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        match self.0 {
+            ValueRef::Null => visitor.visit_none(),
+            ValueRef::Bool(b) => visitor.visit_bool(b),
+            ValueRef::UInt(u) => visitor.visit_u64(u),
+            ValueRef::Int(i) => visitor.visit_i64(i),
+            ValueRef::Float(f) => visitor.visit_f64(f),
+            ValueRef::Str(s) => visitor.visit_str(s),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        match self.0 {
+            ValueRef::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{AttributeValue, MissingInfo, SchedulingClass};
+    use crate::job_events::{JobEvent, JobEventType};
+    use crate::machine_attributes::MachineAttribute;
+    use crate::machine_events::{MachineEvent, MachineEventType};
+    use crate::task_constraints::{ConstraintOperator, TaskConstraint};
+    use crate::task_events::{TaskEvent, TaskEventType};
+    use crate::task_usage::TaskUsageRecord;
+    use serde::de::value::{Error as ValueError, U8Deserializer};
+    use serde::de::IntoDeserializer;
+    use serde::Deserialize;
+
+    fn column(name: &'static str, column_type: ColumnType, nullable: bool) -> Column {
+        Column {
+            name,
+            column_type,
+            nullable,
+        }
+    }
+
+    #[test]
+    fn repr_enums_have_a_zero_variant() {
+        // `columns` hands every field the value 0, so a table with an enum field lacking a 0
+        // variant would have no schema
+        fn zero<'de, E: Deserialize<'de>>() -> Result<E, ValueError> {
+            let d: U8Deserializer<ValueError> = 0u8.into_deserializer();
+            E::deserialize(d)
+        }
+        assert_eq!(
+            zero::<MissingInfo>().unwrap(),
+            MissingInfo::SnapshotButNoTransition
+        );
+        assert_eq!(zero::<SchedulingClass>().unwrap(), SchedulingClass::Class0);
+        assert_eq!(zero::<JobEventType>().unwrap(), JobEventType::Submit);
+        assert_eq!(zero::<MachineEventType>().unwrap(), MachineEventType::Add);
+        assert_eq!(zero::<TaskEventType>().unwrap(), TaskEventType::Submit);
+        assert_eq!(
+            zero::<ConstraintOperator>().unwrap(),
+            ConstraintOperator::Equal
+        );
+
+        columns::<JobEvent>().unwrap();
+        columns::<MachineAttribute>().unwrap();
+        columns::<MachineEvent>().unwrap();
+        columns::<TaskConstraint>().unwrap();
+        columns::<TaskEvent>().unwrap();
+        columns::<TaskUsageRecord>().unwrap();
+    }
+
+    #[test]
+    fn columns_follow_field_types() {
+        assert_eq!(
+            columns::<MachineEvent>().unwrap(),
+            vec![
+                column("time", ColumnType::U64, false),
+                column("machine_id", ColumnType::U64, false),
+                column("event_type", ColumnType::U8, false),
+                column("platform_id", ColumnType::Str, true),
+                column("cpus", ColumnType::F64, true),
+                column("memory", ColumnType::F64, true),
+            ]
+        );
+        // attribute values are strings, and BOOLEAN fields the integers the trace encodes them as
+        assert_eq!(
+            columns::<MachineAttribute>().unwrap(),
+            vec![
+                column("time", ColumnType::U64, false),
+                column("machine_id", ColumnType::U64, false),
+                column("attribute_name", ColumnType::Str, false),
+                column("attribute_value", ColumnType::Str, true),
+                column("attribute_deleted", ColumnType::U8, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_round_trip() {
+        let attribute = MachineAttribute {
+            time: 5,
+            machine_id: 1,
+            attribute_name: "rack".to_string(),
+            attribute_value: Some(AttributeValue::Integer(-3)),
+            attribute_deleted: true,
+        };
+        let row = to_row(&attribute).unwrap();
+        assert_eq!(
+            row,
+            vec![
+                Value::UInt(5),
+                Value::UInt(1),
+                Value::Str("rack".to_string()),
+                Value::Str("-3".to_string()),
+                Value::UInt(1),
+            ]
+        );
+        let back: MachineAttribute = from_row(&row).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", attribute));
+
+        let event = TaskEvent {
+            time: 0,
+            missing_info: None,
+            job_id: 1,
+            task_index: 2,
+            machine_id: None,
+            event_type: TaskEventType::UpdateRunning,
+            user: None,
+            scheduling_class: Some(SchedulingClass::Unknown),
+            priority: 11,
+            cpu_request: Some(0.5),
+            memory_request: None,
+            disk_space_request: None,
+            different_machines_restrict: None,
+        };
+        let row = to_row(&event).unwrap();
+        assert_eq!(row[1], Value::Null);
+        assert_eq!(row[5], Value::UInt(8));
+        assert_eq!(row[7], Value::UInt(9));
+        let back: TaskEvent = from_row(&row).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", event));
+    }
+
+    #[test]
+    fn mismatched_rows_are_rejected() {
+        let row = vec![
+            Value::UInt(0),
+            Value::UInt(1),
+            Value::UInt(7),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+        ];
+        // no event type 7
+        assert!(from_row::<MachineEvent>(&row).is_err());
+        // a missing mandatory field
+        assert!(from_row::<MachineEvent>(&[Value::Null, Value::UInt(1)]).is_err());
+        // a string for an integer
+        let row = vec![Value::Str("0".to_string()), Value::UInt(1)];
+        assert!(from_row::<MachineEvent>(&row).is_err());
+    }
+}
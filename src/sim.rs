@@ -0,0 +1,742 @@
+use crate::common::{SchedulingClass, AFTER_TRACE_TIME};
+use crate::distribution::{Distribution, GroupedDistribution};
+use crate::error::Error;
+use crate::job_events::{JobEvent, JobEventType};
+use crate::machine_events::{MachineEvent, MachineEventType};
+use crate::machine_timeline::Capacity;
+use crate::merge::{MergedEventIterator, Table, TraceEvent};
+use crate::slack::PriorityBand;
+use crate::task_attempts::TaskAttemptIterator;
+use crate::task_events::{TaskEvent, TaskEventIterator, TaskEventType};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+/// The tables a simulation replays.
+pub static SIM_TABLES: [Table; 3] = [Table::MachineEvents, Table::JobEvents, Table::TaskEvents];
+
+/// A task as submitted to the simulated cluster.
+#[derive(Clone, Debug)]
+pub struct SimTask {
+    pub job_id: u64,
+    pub task_index: u64,
+    pub submit_time: u64,
+    pub priority: u8,
+    pub scheduling_class: Option<SchedulingClass>,
+    /// The task's requests; missing requests count as zero.
+    pub cpu_request: f64,
+    pub memory_request: f64,
+    /// How long the task runs once placed, or `None` if it runs until the end of the simulation.
+    pub runtime: Option<u64>,
+}
+
+impl SimTask {
+    pub fn key(&self) -> (u64, u64) {
+        (self.job_id, self.task_index)
+    }
+}
+
+/// A task waiting to be placed.
+#[derive(Clone, Debug)]
+pub struct PendingTask {
+    pub task: SimTask,
+    /// When the task (re-)entered the queue: its submission, or its latest preemption.
+    pub queued_since: u64,
+}
+
+/// A task placed on a machine.
+#[derive(Clone, Debug)]
+pub struct RunningTask {
+    pub task: SimTask,
+    pub machine_id: u64,
+    pub start_time: u64,
+}
+
+/// A machine in the simulated cluster.
+#[derive(Clone, Debug, Default)]
+pub struct SimMachine {
+    pub capacity: Capacity,
+    /// The summed requests of the tasks placed on the machine.
+    pub allocated: Capacity,
+    pub tasks: HashSet<(u64, u64)>,
+}
+
+impl SimMachine {
+    /// The unallocated capacity; negative if the machine shrank below its allocation.
+    pub fn free(&self) -> Capacity {
+        Capacity {
+            cpus: self.capacity.cpus - self.allocated.cpus,
+            memory: self.capacity.memory - self.allocated.memory,
+        }
+    }
+
+    /// Whether the machine has room for a task's requests.
+    pub fn fits(&self, task: &SimTask) -> bool {
+        let free = self.free();
+        task.cpu_request <= free.cpus && task.memory_request <= free.memory
+    }
+}
+
+/// The state of the simulated cluster that a `Scheduler` decides on.
+#[derive(Clone, Debug, Default)]
+pub struct SimCluster {
+    machines: BTreeMap<u64, SimMachine>,
+    running: HashMap<(u64, u64), RunningTask>,
+    totals: Allocation,
+}
+
+impl SimCluster {
+    /// The machines, in order of their IDs.
+    pub fn machines(&self) -> &BTreeMap<u64, SimMachine> {
+        &self.machines
+    }
+
+    /// The running tasks, by job ID and task index.
+    pub fn running(&self) -> &HashMap<(u64, u64), RunningTask> {
+        &self.running
+    }
+
+    /// The total capacity and allocation of all machines.
+    pub fn totals(&self) -> Allocation {
+        self.totals
+    }
+
+    /// Sets a machine's capacity, adding the machine if it is new.
+    fn set_capacity(&mut self, machine_id: u64, capacity: Capacity) {
+        let m = self.machines.entry(machine_id).or_default();
+        self.totals.capacity.cpus += capacity.cpus - m.capacity.cpus;
+        self.totals.capacity.memory += capacity.memory - m.capacity.memory;
+        m.capacity = capacity;
+    }
+
+    /// Removes a machine, which must have no tasks left.
+    fn remove_machine(&mut self, machine_id: u64) {
+        if let Some(m) = self.machines.remove(&machine_id) {
+            debug_assert!(m.tasks.is_empty());
+            self.totals.capacity.cpus -= m.capacity.cpus;
+            self.totals.capacity.memory -= m.capacity.memory;
+        }
+        if self.machines.is_empty() {
+            // don't carry rounding errors over into the next machines
+            self.totals = Allocation::default();
+        }
+    }
+
+    fn place(&mut self, task: SimTask, machine_id: u64, time: u64) {
+        let m = self.machines.get_mut(&machine_id).expect("machine exists");
+        m.allocated.cpus += task.cpu_request;
+        m.allocated.memory += task.memory_request;
+        m.tasks.insert(task.key());
+        self.totals.allocated.cpus += task.cpu_request;
+        self.totals.allocated.memory += task.memory_request;
+        self.running.insert(
+            task.key(),
+            RunningTask {
+                task,
+                machine_id,
+                start_time: time,
+            },
+        );
+    }
+
+    fn unplace(&mut self, key: (u64, u64)) -> Option<RunningTask> {
+        let r = self.running.remove(&key)?;
+        if let Some(m) = self.machines.get_mut(&r.machine_id) {
+            m.allocated.cpus -= r.task.cpu_request;
+            m.allocated.memory -= r.task.memory_request;
+            m.tasks.remove(&key);
+        }
+        if self.running.is_empty() {
+            self.totals.allocated = Capacity::default();
+        } else {
+            self.totals.allocated.cpus -= r.task.cpu_request;
+            self.totals.allocated.memory -= r.task.memory_request;
+        }
+        Some(r)
+    }
+}
+
+/// A scheduler's decision about one task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Place a pending task on a machine.
+    Place { task: (u64, u64), machine_id: u64 },
+    /// Evict a running task and return it to the queue; it restarts from scratch when placed again.
+    Preempt { task: (u64, u64) },
+}
+
+/// A cluster scheduling policy under evaluation.
+pub trait Scheduler {
+    /// Decides what to do at `time`, given the cluster state and the queue of pending tasks in
+    /// arrival order. Called once per simulated timestamp at which something happened and tasks
+    /// are pending. Decisions are applied in order; invalid ones (e.g., placing a task on a machine
+    /// without room for it) are counted and ignored.
+    fn schedule(
+        &mut self,
+        time: u64,
+        cluster: &SimCluster,
+        pending: &[PendingTask],
+    ) -> Vec<Decision>;
+
+    /// Called when a job is submitted, before any of its tasks.
+    fn job_submitted(&mut self, _time: u64, _job: &JobEvent) {}
+}
+
+/// Places tasks in priority order on the machine with the lowest ID that has room, and never
+/// preempts.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FirstFit;
+
+impl Scheduler for FirstFit {
+    fn schedule(
+        &mut self,
+        _time: u64,
+        cluster: &SimCluster,
+        pending: &[PendingTask],
+    ) -> Vec<Decision> {
+        let mut queue: Vec<&PendingTask> = pending.iter().collect();
+        queue.sort_by_key(|p| (Reverse(p.task.priority), p.queued_since));
+
+        // the allocations of the machines placed on so far, with the same arithmetic as the
+        // simulation's own check
+        let mut allocated: HashMap<u64, Capacity> = HashMap::new();
+        let mut decisions = Vec::new();
+        for p in queue {
+            let task = &p.task;
+            let found = cluster.machines().iter().find(|(id, m)| {
+                let a = allocated.get(id).unwrap_or(&m.allocated);
+                task.cpu_request <= m.capacity.cpus - a.cpus
+                    && task.memory_request <= m.capacity.memory - a.memory
+            });
+            if let Some((id, m)) = found {
+                let a = allocated.entry(*id).or_insert(m.allocated);
+                a.cpus += task.cpu_request;
+                a.memory += task.memory_request;
+                decisions.push(Decision::Place {
+                    task: task.key(),
+                    machine_id: *id,
+                });
+            }
+        }
+        decisions
+    }
+}
+
+/// The cluster's total capacity and the part of it allocated to running tasks.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Allocation {
+    pub capacity: Capacity,
+    pub allocated: Capacity,
+}
+
+/// The outcome of a simulated run.
+#[derive(Clone, Debug)]
+pub struct SimulationReport {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub submitted: u64,
+    /// Placements, including repeated placements of preempted tasks.
+    pub placements: u64,
+    pub completed: u64,
+    /// Tasks evicted by the scheduler's decisions.
+    pub preemptions: u64,
+    pub preemptions_by_band: HashMap<PriorityBand, u64>,
+    /// Tasks evicted because their machine was removed.
+    pub machine_evictions: u64,
+    /// Decisions that were invalid when the scheduler made them.
+    pub rejected_decisions: u64,
+    pub pending_at_end: u64,
+    pub running_at_end: u64,
+    /// Time from entering the queue to placement, in seconds.
+    pub queueing_delay: Distribution,
+    pub queueing_delay_by_band: GroupedDistribution<PriorityBand>,
+    /// The cluster's allocation over time, as a step function.
+    pub allocation: Vec<(u64, Allocation)>,
+}
+
+impl SimulationReport {
+    fn new() -> Self {
+        SimulationReport {
+            start_time: 0,
+            end_time: 0,
+            submitted: 0,
+            placements: 0,
+            completed: 0,
+            preemptions: 0,
+            preemptions_by_band: HashMap::new(),
+            machine_evictions: 0,
+            rejected_decisions: 0,
+            pending_at_end: 0,
+            running_at_end: 0,
+            queueing_delay: Distribution::default(),
+            queueing_delay_by_band: GroupedDistribution::default(),
+            allocation: Vec::new(),
+        }
+    }
+
+    /// The time-weighted mean fractions of the cluster's CPU and memory capacity that were
+    /// allocated over the run.
+    pub fn mean_utilization(&self) -> (f64, f64) {
+        let (mut cpus, mut memory, mut cpu_capacity, mut memory_capacity) = (0.0, 0.0, 0.0, 0.0);
+        let ends = self
+            .allocation
+            .iter()
+            .skip(1)
+            .map(|(t, _)| *t)
+            .chain(Some(self.end_time));
+        for ((t, a), end) in self.allocation.iter().zip(ends) {
+            let dt = end.saturating_sub(*t) as f64;
+            cpus += a.allocated.cpus * dt;
+            memory += a.allocated.memory * dt;
+            cpu_capacity += a.capacity.cpus * dt;
+            memory_capacity += a.capacity.memory * dt;
+        }
+        let frac = |n: f64, d: f64| if d > 0.0 { n / d } else { 0.0 };
+        (frac(cpus, cpu_capacity), frac(memory, memory_capacity))
+    }
+}
+
+/// The SCHEDULE→FINISH duration of every task in the trace at `trace_path` that finished, from
+/// its last finished attempt. Attempts that finished after the trace window have no known
+/// runtime and are left out.
+pub fn finished_runtimes(trace_path: &str) -> Result<HashMap<(u64, u64), u64>, Error> {
+    let mut runtimes = HashMap::new();
+    for a in TaskAttemptIterator::<TaskEventIterator>::new(trace_path)? {
+        let a = a?;
+        if a.terminal_event == Some(TaskEventType::Finish) {
+            if let Some(runtime) = a.runtime() {
+                runtimes.insert((a.job_id, a.task_index), runtime);
+            }
+        }
+    }
+    Ok(runtimes)
+}
+
+/// Simulates `scheduler` on the workload and machines of the trace at `trace_path`.
+pub fn simulate<S: Scheduler>(trace_path: &str, scheduler: S) -> Result<SimulationReport, Error> {
+    Simulation::new(scheduler, finished_runtimes(trace_path)?)
+        .run(MergedEventIterator::new(trace_path, &SIM_TABLES)?)
+}
+
+/// A task's scheduled completion: the time, the task, and which of its placements completes.
+type Finish = (u64, (u64, u64), u64);
+
+/// A discrete-event simulation of a cluster run by a `Scheduler`.
+///
+/// The simulation replays machine events and job and task SUBMIT events from the trace. Each
+/// task is submitted once, at its first SUBMIT, and runs for its runtime in the trace once
+/// placed; its other trace events are ignored, since the simulated scheduler decides its fate.
+/// Tasks that never finished in the trace run until the end of the simulation. Removing a machine
+/// returns its tasks to the queue. Events after the trace window (at `AFTER_TRACE_TIME`) are
+/// skipped, so the simulation ends at the last event within the window or the last finish.
+pub struct Simulation<S> {
+    scheduler: S,
+    runtimes: HashMap<(u64, u64), u64>,
+    cluster: SimCluster,
+    pending: Vec<PendingTask>,
+    submitted: HashSet<(u64, u64)>,
+    finishes: BinaryHeap<Reverse<Finish>>,
+    placements: HashMap<(u64, u64), u64>,
+    report: SimulationReport,
+}
+
+impl<S: Scheduler> Simulation<S> {
+    /// Creates a simulation of an empty cluster, with task runtimes given by `runtimes`.
+    pub fn new(scheduler: S, runtimes: HashMap<(u64, u64), u64>) -> Self {
+        Simulation {
+            scheduler,
+            runtimes,
+            cluster: SimCluster::default(),
+            pending: Vec::new(),
+            submitted: HashSet::new(),
+            finishes: BinaryHeap::new(),
+            placements: HashMap::new(),
+            report: SimulationReport::new(),
+        }
+    }
+
+    /// Runs the simulation over a stream of events in time order, until the events are exhausted
+    /// and all tasks with known runtimes have finished.
+    pub fn run<I>(mut self, events: I) -> Result<SimulationReport, Error>
+    where
+        I: IntoIterator<Item = Result<TraceEvent, Error>>,
+    {
+        let mut events = events.into_iter().peekable();
+        let mut first = true;
+        loop {
+            let next_event = match events.peek() {
+                Some(Ok(e)) if e.time() >= AFTER_TRACE_TIME => {
+                    events.next();
+                    continue;
+                }
+                Some(Ok(e)) => Some(e.time()),
+                Some(Err(_)) => return Err(events.next().unwrap().unwrap_err()),
+                None => None,
+            };
+            let next_finish = self.finishes.peek().map(|Reverse((t, _, _))| *t);
+            let time = match (next_event, next_finish) {
+                (Some(e), Some(f)) => e.min(f),
+                (Some(t), None) | (None, Some(t)) => t,
+                (None, None) => break,
+            };
+            if first {
+                self.report.start_time = time;
+                first = false;
+            }
+            self.report.end_time = time;
+
+            // finishing tasks free their resources before anything else happens at `time`
+            while let Some(Reverse((t, key, placement))) = self.finishes.peek().cloned() {
+                if t != time {
+                    break;
+                }
+                self.finishes.pop();
+                // a finish is stale if the task was evicted since the placement it belongs to
+                if self.placements.get(&key) == Some(&placement)
+                    && self.cluster.unplace(key).is_some()
+                {
+                    self.report.completed += 1;
+                }
+            }
+            while let Some(Ok(e)) = events.peek() {
+                if e.time() != time {
+                    break;
+                }
+                match events.next().unwrap()? {
+                    TraceEvent::Machine(e) => self.apply_machine_event(&e),
+                    TraceEvent::Job(e) if e.event_type == JobEventType::Submit => {
+                        self.scheduler.job_submitted(time, &e)
+                    }
+                    TraceEvent::Task(e) => self.apply_task_event(&e),
+                    _ => (),
+                }
+            }
+            if !self.pending.is_empty() {
+                let decisions = self.scheduler.schedule(time, &self.cluster, &self.pending);
+                self.apply_decisions(time, decisions);
+            }
+            self.record_allocation(time);
+        }
+        self.report.pending_at_end = self.pending.len() as u64;
+        self.report.running_at_end = self.cluster.running.len() as u64;
+        Ok(self.report)
+    }
+
+    fn apply_machine_event(&mut self, e: &MachineEvent) {
+        match e.event_type {
+            MachineEventType::Add | MachineEventType::Update => {
+                let old = self
+                    .cluster
+                    .machines
+                    .get(&e.machine_id)
+                    .map(|m| m.capacity)
+                    .unwrap_or_default();
+                let capacity = Capacity {
+                    cpus: e.cpus.unwrap_or(old.cpus),
+                    memory: e.memory.unwrap_or(old.memory),
+                };
+                self.cluster.set_capacity(e.machine_id, capacity);
+            }
+            MachineEventType::Remove => {
+                let tasks: Vec<(u64, u64)> = match self.cluster.machines.get(&e.machine_id) {
+                    Some(m) => m.tasks.iter().cloned().collect(),
+                    None => return,
+                };
+                for key in tasks {
+                    if let Some(p) = self.evict(key, e.time) {
+                        self.pending.push(p);
+                        self.report.machine_evictions += 1;
+                    }
+                }
+                self.cluster.remove_machine(e.machine_id);
+            }
+        }
+    }
+
+    fn apply_task_event(&mut self, e: &TaskEvent) {
+        let key = (e.job_id, e.task_index);
+        if e.event_type != TaskEventType::Submit || !self.submitted.insert(key) {
+            return;
+        }
+        self.report.submitted += 1;
+        self.pending.push(PendingTask {
+            task: SimTask {
+                job_id: e.job_id,
+                task_index: e.task_index,
+                submit_time: e.time,
+                priority: e.priority,
+                scheduling_class: e.scheduling_class,
+                cpu_request: e.cpu_request.unwrap_or(0.0),
+                memory_request: e.memory_request.unwrap_or(0.0),
+                runtime: self.runtimes.get(&key).cloned(),
+            },
+            queued_since: e.time,
+        });
+    }
+
+    /// Evicts a running task, returning it as a pending task queued since `time`. The task's
+    /// scheduled finish no longer applies.
+    fn evict(&mut self, key: (u64, u64), time: u64) -> Option<PendingTask> {
+        let r = self.cluster.unplace(key)?;
+        *self.placements.entry(key).or_insert(0) += 1;
+        Some(PendingTask {
+            task: r.task,
+            queued_since: time,
+        })
+    }
+
+    fn apply_decisions(&mut self, time: u64, decisions: Vec<Decision>) {
+        let mut placed = HashSet::new();
+        let mut preempted = Vec::new();
+        let index: HashMap<(u64, u64), usize> = self
+            .pending
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.task.key(), i))
+            .collect();
+        for d in decisions {
+            match d {
+                Decision::Place { task, machine_id } => {
+                    let p = match index.get(&task) {
+                        Some(i) if !placed.contains(&task) => &self.pending[*i],
+                        _ => {
+                            self.report.rejected_decisions += 1;
+                            continue;
+                        }
+                    };
+                    match self.cluster.machines.get(&machine_id) {
+                        Some(m) if m.fits(&p.task) => (),
+                        _ => {
+                            self.report.rejected_decisions += 1;
+                            continue;
+                        }
+                    }
+                    let delay = (time - p.queued_since) as f64 / 1_000_000.0;
+                    let band = PriorityBand::of(p.task.priority);
+                    self.report.queueing_delay.record(delay);
+                    self.report.queueing_delay_by_band.record(band, delay);
+                    self.report.placements += 1;
+
+                    let placement = self.placements.entry(task).or_insert(0);
+                    *placement += 1;
+                    if let Some(runtime) = p.task.runtime {
+                        self.finishes
+                            .push(Reverse((time + runtime, task, *placement)));
+                    }
+                    self.cluster.place(p.task.clone(), machine_id, time);
+                    placed.insert(task);
+                }
+                Decision::Preempt { task } => match self.evict(task, time) {
+                    Some(p) => {
+                        self.report.preemptions += 1;
+                        *self
+                            .report
+                            .preemptions_by_band
+                            .entry(PriorityBand::of(p.task.priority))
+                            .or_insert(0) += 1;
+                        preempted.push(p);
+                    }
+                    None => self.report.rejected_decisions += 1,
+                },
+            }
+        }
+        if !placed.is_empty() {
+            self.pending.retain(|p| !placed.contains(&p.task.key()));
+        }
+        // preempted tasks rejoin the back of the queue
+        self.pending.extend(preempted);
+    }
+
+    fn record_allocation(&mut self, time: u64) {
+        let a = self.cluster.totals();
+        match self.report.allocation.last() {
+            Some((_, last)) if *last == a => (),
+            _ => self.report.allocation.push((time, a)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{scratch_dir, write_part};
+
+    fn machine_event(time: u64, machine_id: u64, event_type: MachineEventType) -> TraceEvent {
+        TraceEvent::Machine(MachineEvent {
+            time,
+            machine_id,
+            event_type,
+            platform_id: None,
+            cpus: Some(1.0),
+            memory: Some(1.0),
+        })
+    }
+
+    fn submit(time: u64, task_index: u64, priority: u8) -> TraceEvent {
+        TraceEvent::Task(TaskEvent {
+            time,
+            missing_info: None,
+            job_id: 1,
+            task_index,
+            machine_id: None,
+            event_type: TaskEventType::Submit,
+            user: None,
+            scheduling_class: None,
+            priority,
+            cpu_request: Some(0.6),
+            memory_request: Some(0.1),
+            disk_space_request: None,
+            different_machines_restrict: None,
+        })
+    }
+
+    fn run<S: Scheduler>(
+        scheduler: S,
+        runtimes: &[((u64, u64), u64)],
+        events: Vec<TraceEvent>,
+    ) -> SimulationReport {
+        Simulation::new(scheduler, runtimes.iter().cloned().collect())
+            .run(events.into_iter().map(Ok))
+            .unwrap()
+    }
+
+    /// First fit, but preempting everything running at the given time.
+    struct PreemptAt(u64);
+
+    impl Scheduler for PreemptAt {
+        fn schedule(
+            &mut self,
+            time: u64,
+            cluster: &SimCluster,
+            pending: &[PendingTask],
+        ) -> Vec<Decision> {
+            let mut decisions = Vec::new();
+            if time == self.0 {
+                let mut running: Vec<(u64, u64)> = cluster.running().keys().cloned().collect();
+                running.sort_unstable();
+                decisions.extend(running.into_iter().map(|task| Decision::Preempt { task }));
+                // place the newest task, which the preemption made room for
+                let p = pending.last().unwrap();
+                decisions.push(Decision::Place {
+                    task: p.task.key(),
+                    machine_id: 1,
+                });
+                return decisions;
+            }
+            FirstFit.schedule(time, cluster, pending)
+        }
+    }
+
+    #[test]
+    fn preempted_task_completes_once() {
+        // task 0 is preempted at 10, and only fits again when task 1 finishes at 210; its stale
+        // finish at 100 must not count
+        let report = run(
+            PreemptAt(10),
+            &[((1, 0), 100), ((1, 1), 200)],
+            vec![
+                machine_event(0, 1, MachineEventType::Add),
+                submit(0, 0, 0),
+                submit(10, 1, 9),
+            ],
+        );
+        assert_eq!(report.preemptions, 1);
+        assert_eq!(report.placements, 3);
+        assert_eq!(report.completed, 2);
+        assert_eq!(report.end_time, 310);
+        assert_eq!(report.running_at_end, 0);
+        assert_eq!(report.pending_at_end, 0);
+    }
+
+    #[test]
+    fn machine_removal_requeues_tasks() {
+        // task 0 loses its machine at 10 and waits for the next machine, added at 150
+        let report = run(
+            FirstFit,
+            &[((1, 0), 100)],
+            vec![
+                machine_event(0, 1, MachineEventType::Add),
+                submit(0, 0, 0),
+                machine_event(10, 1, MachineEventType::Remove),
+                machine_event(150, 2, MachineEventType::Add),
+            ],
+        );
+        assert_eq!(report.machine_evictions, 1);
+        assert_eq!(report.placements, 2);
+        assert_eq!(report.completed, 1);
+        assert_eq!(report.end_time, 250);
+        assert_eq!(report.running_at_end, 0);
+
+        let capacity = |cpus| Capacity { cpus, memory: cpus };
+        let allocation: Vec<(u64, Capacity, f64)> = report
+            .allocation
+            .iter()
+            .map(|(t, a)| (*t, a.capacity, a.allocated.cpus))
+            .collect();
+        assert_eq!(
+            allocation,
+            vec![
+                (0, capacity(1.0), 0.6),
+                (10, capacity(0.0), 0.0),
+                (150, capacity(1.0), 0.6),
+                (250, capacity(1.0), 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_after_the_trace_are_skipped() {
+        let report = run(
+            FirstFit,
+            &[],
+            vec![
+                machine_event(600, 1, MachineEventType::Add),
+                submit(600, 0, 0),
+                machine_event(900, 1, MachineEventType::Update),
+                machine_event(AFTER_TRACE_TIME, 1, MachineEventType::Remove),
+            ],
+        );
+        assert_eq!((report.start_time, report.end_time), (600, 900));
+        assert_eq!(report.machine_evictions, 0);
+        assert_eq!(report.running_at_end, 1);
+        assert_eq!(report.mean_utilization(), (0.6, 0.1));
+
+        // only events after the window
+        let add = machine_event(AFTER_TRACE_TIME, 1, MachineEventType::Add);
+        let report = run(FirstFit, &[], vec![add]);
+        assert!(report.allocation.is_empty());
+        assert_eq!(report.end_time, 0);
+    }
+
+    #[test]
+    fn tasks_finishing_after_the_trace_have_no_runtime() {
+        // task 0 runs from 600s until after the trace window, task 1 for 100s
+        let trace = scratch_dir();
+        let part = "part-00000-of-00001.csv";
+        write_part(&trace, "machine_events", part, &["0,1,0,p1,1,1"]);
+        write_part(&trace, "job_events", part, &["600000000,,1,0,u1,1,j1,l1"]);
+        let finish = format!("{},,1,0,1,4,u1,1,9,,,,0", AFTER_TRACE_TIME);
+        write_part(
+            &trace,
+            "task_events",
+            part,
+            &[
+                "600000000,,1,0,,0,u1,1,9,0.25,0.25,,0",
+                "600000000,,1,1,,0,u1,1,9,0.25,0.25,,0",
+                "600000000,,1,0,1,1,u1,1,9,,,,0",
+                "600000000,,1,1,1,1,u1,1,9,,,,0",
+                "700000000,,1,1,1,4,u1,1,9,,,,0",
+                &finish,
+            ],
+        );
+        let trace = trace.to_str().unwrap();
+        let runtimes = finished_runtimes(trace).unwrap();
+        assert_eq!(runtimes, vec![((1, 1), 100_000_000)].into_iter().collect());
+
+        let report = simulate(trace, FirstFit).unwrap();
+        assert_eq!(report.end_time, 700_000_000);
+        assert_eq!((report.completed, report.running_at_end), (1, 1));
+        // both tasks hold half the machine for the last 100s of 700s
+        let (cpus, _) = report.mean_utilization();
+        assert!((cpus - 0.5 / 7.0).abs() < 1e-9);
+    }
+}
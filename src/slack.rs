@@ -0,0 +1,365 @@
+use crate::common::{AFTER_TRACE_TIME, TRACE_START_TIME};
+use crate::error::Error;
+use crate::merge::{MergedEventIterator, Table, TraceEvent};
+use crate::task_events::{TaskEvent, TaskEventType};
+use crate::task_usage::TaskUsageRecord;
+use crate::utilization::bucket_overlaps;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// The priority bands that the trace documentation groups task priorities into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PriorityBand {
+    /// Priorities 0 and 1: tasks that are not charged for their resources.
+    Free,
+    /// Priorities 2 to 8: batch and other best-effort work.
+    Batch,
+    /// Priorities 9 and 10: latency-sensitive production services.
+    Production,
+    /// Priority 11: monitoring.
+    Monitoring,
+}
+
+impl PriorityBand {
+    pub const ALL: [PriorityBand; 4] = [
+        PriorityBand::Free,
+        PriorityBand::Batch,
+        PriorityBand::Production,
+        PriorityBand::Monitoring,
+    ];
+
+    pub fn of(priority: u8) -> Self {
+        match priority {
+            0..=1 => PriorityBand::Free,
+            2..=8 => PriorityBand::Batch,
+            9..=10 => PriorityBand::Production,
+            _ => PriorityBand::Monitoring,
+        }
+    }
+}
+
+impl fmt::Display for PriorityBand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PriorityBand::Free => "free",
+            PriorityBand::Batch => "batch",
+            PriorityBand::Production => "production",
+            PriorityBand::Monitoring => "monitoring",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The resources requested by and used by the running tasks of a priority band, averaged over a
+/// time bucket, in the trace's normalized units.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Slack {
+    pub cpu_request: f64,
+    pub memory_request: f64,
+    pub cpu_usage: f64,
+    /// Canonical memory usage.
+    pub memory_usage: f64,
+}
+
+impl Slack {
+    /// The requested CPU that went unused (negative if usage exceeded requests).
+    pub fn cpu_slack(&self) -> f64 {
+        self.cpu_request - self.cpu_usage
+    }
+
+    /// The requested memory that went unused (negative if usage exceeded requests).
+    pub fn memory_slack(&self) -> f64 {
+        self.memory_request - self.memory_usage
+    }
+}
+
+/// The latest priority and requests of a task, and when it started running, if it is.
+struct TaskRequests {
+    priority: u8,
+    cpu_request: Option<f64>,
+    memory_request: Option<f64>,
+    running_since: Option<u64>,
+}
+
+/// Requested versus used resources per time bucket and priority band.
+///
+/// Requests are those of the tasks running (between their SCHEDULE and terminal events) during a
+/// bucket, and usage comes from the task usage table; both are weighted by their overlap with the
+/// bucket. Usage records are attributed to the band of the task's priority when the measurement
+/// period starts.
+///
+/// Task events at time 0, which describe the cluster at the start of the trace window, count from
+/// `TRACE_START_TIME`; events after the window (at `AFTER_TRACE_TIME`) count at the end of the
+/// analysis.
+pub struct SlackAnalysis {
+    bucket_width: u64,
+    buckets: BTreeMap<u64, [Slack; 4]>,
+    tasks: HashMap<(u64, u64), TaskRequests>,
+    /// The latest timestamp seen, other than `AFTER_TRACE_TIME`.
+    end_time: u64,
+    /// The time after which nothing is counted, if given.
+    trace_end: Option<u64>,
+    unattributed: u64,
+}
+
+impl SlackAnalysis {
+    /// Analyzes the task events and task usage of the trace at `trace_path`, using buckets of
+    /// `bucket_width` microseconds.
+    pub fn new(trace_path: &str, bucket_width: u64) -> Result<Self, Error> {
+        Self::from_events(
+            MergedEventIterator::new(trace_path, &[Table::TaskEvents, Table::TaskUsage])?,
+            bucket_width,
+        )
+    }
+
+    /// Analyzes a stream of task events and task usage records in time order; other events are
+    /// ignored. Tasks still running at the end of the stream are counted until the last timestamp
+    /// seen.
+    pub fn from_events<I>(events: I, bucket_width: u64) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Result<TraceEvent, Error>>,
+    {
+        Self::analyze(events, bucket_width, None)
+    }
+
+    /// Like `from_events`, but counts nothing after `trace_end`, and tasks still running at the
+    /// end of the stream until `trace_end`.
+    pub fn from_events_until<I>(events: I, bucket_width: u64, trace_end: u64) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Result<TraceEvent, Error>>,
+    {
+        Self::analyze(events, bucket_width, Some(trace_end))
+    }
+
+    fn analyze<I>(events: I, bucket_width: u64, trace_end: Option<u64>) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Result<TraceEvent, Error>>,
+    {
+        assert!(bucket_width > 0, "bucket width must be positive");
+        let mut analysis = SlackAnalysis {
+            bucket_width,
+            buckets: BTreeMap::new(),
+            tasks: HashMap::new(),
+            end_time: 0,
+            trace_end,
+            unattributed: 0,
+        };
+        for e in events {
+            match e? {
+                TraceEvent::Task(e) => analysis.apply_task_event(&e),
+                TraceEvent::TaskUsage(u) => analysis.add_usage(&u),
+                _ => (),
+            }
+        }
+        let end_time = trace_end.unwrap_or(analysis.end_time);
+        let keys: Vec<(u64, u64)> = analysis.tasks.keys().cloned().collect();
+        for key in keys {
+            analysis.close(key, end_time);
+        }
+        analysis.tasks.clear();
+        Ok(analysis)
+    }
+
+    /// The time at which a task event at `time` starts or ends a request interval.
+    fn interval_time(&self, time: u64) -> u64 {
+        let time = if time == 0 {
+            TRACE_START_TIME
+        } else if time >= AFTER_TRACE_TIME {
+            self.end_time
+        } else {
+            time
+        };
+        self.trace_end.map_or(time, |end| time.min(end))
+    }
+
+    /// Adds the requests of a running task from when it started running (or its requests were
+    /// last updated) until `time`.
+    fn close(&mut self, key: (u64, u64), time: u64) {
+        let t = match self.tasks.get_mut(&key) {
+            Some(t) => t,
+            None => return,
+        };
+        let since = match t.running_since.take() {
+            Some(since) => since,
+            None => return,
+        };
+        let band = PriorityBand::of(t.priority) as usize;
+        let (cpu, memory) = (
+            t.cpu_request.unwrap_or(0.0),
+            t.memory_request.unwrap_or(0.0),
+        );
+        for (bucket_start, weight) in bucket_overlaps(since, time, self.bucket_width) {
+            let s = &mut self.buckets.entry(bucket_start).or_default()[band];
+            s.cpu_request += cpu * weight;
+            s.memory_request += memory * weight;
+        }
+    }
+
+    fn apply_task_event(&mut self, e: &TaskEvent) {
+        let key = (e.job_id, e.task_index);
+        if e.time < AFTER_TRACE_TIME {
+            self.end_time = self.end_time.max(e.time);
+        }
+        let time = self.interval_time(e.time);
+        // requests change at the event, so the running period so far is accounted first
+        self.close(key, time);
+        let t = self.tasks.entry(key).or_insert_with(|| TaskRequests {
+            priority: e.priority,
+            cpu_request: None,
+            memory_request: None,
+            running_since: None,
+        });
+        t.priority = e.priority;
+        t.cpu_request = e.cpu_request.or(t.cpu_request);
+        t.memory_request = e.memory_request.or(t.memory_request);
+        match e.event_type {
+            TaskEventType::Schedule | TaskEventType::UpdateRunning => t.running_since = Some(time),
+            _ => (),
+        }
+    }
+
+    fn add_usage(&mut self, u: &TaskUsageRecord) {
+        self.end_time = self.end_time.max(u.end_time);
+        let band = match self.tasks.get(&(u.job_id, u.task_index)) {
+            Some(t) => PriorityBand::of(t.priority) as usize,
+            None => {
+                self.unattributed += 1;
+                return;
+            }
+        };
+        let (cpu, memory) = (
+            u.cpu_rate.unwrap_or(0.0),
+            u.canonical_mem_usage.unwrap_or(0.0),
+        );
+        let end = self.trace_end.map_or(u.end_time, |end| u.end_time.min(end));
+        for (bucket_start, weight) in bucket_overlaps(u.start_time, end, self.bucket_width) {
+            let s = &mut self.buckets.entry(bucket_start).or_default()[band];
+            s.cpu_usage += cpu * weight;
+            s.memory_usage += memory * weight;
+        }
+    }
+
+    pub fn bucket_width(&self) -> u64 {
+        self.bucket_width
+    }
+
+    /// The number of usage records whose task had no preceding task events, and which therefore
+    /// could not be attributed to a priority band.
+    pub fn unattributed_usage_records(&self) -> u64 {
+        self.unattributed
+    }
+
+    /// The requests and usage of a priority band, as `(bucket start time, slack)` pairs in time
+    /// order. Buckets without any running tasks or usage are omitted.
+    pub fn band(&self, band: PriorityBand) -> impl Iterator<Item = (u64, &Slack)> {
+        self.buckets
+            .iter()
+            .map(move |(b, s)| (*b, &s[band as usize]))
+    }
+
+    /// The requests and usage of every band, as `(bucket start time, band, slack)` triples in time
+    /// and band order.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, PriorityBand, &Slack)> {
+        self.buckets.iter().flat_map(|(b, s)| {
+            PriorityBand::ALL
+                .iter()
+                .zip(s.iter())
+                .map(move |(band, s)| (*b, *band, s))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: u64 = 60_000_000;
+
+    fn task_event(time: u64, event_type: TaskEventType) -> TraceEvent {
+        TraceEvent::Task(TaskEvent {
+            time,
+            missing_info: None,
+            job_id: 1,
+            task_index: 0,
+            machine_id: Some(1),
+            event_type,
+            user: None,
+            scheduling_class: None,
+            priority: 9,
+            cpu_request: Some(0.5),
+            memory_request: Some(0.25),
+            disk_space_request: None,
+            different_machines_restrict: None,
+        })
+    }
+
+    fn usage(start_time: u64, end_time: u64) -> TraceEvent {
+        TraceEvent::TaskUsage(TaskUsageRecord {
+            start_time,
+            end_time,
+            job_id: 1,
+            task_index: 0,
+            machine_id: 1,
+            cpu_rate: Some(0.25),
+            canonical_mem_usage: None,
+            assigned_mem_usage: None,
+            unmapped_page_cache: None,
+            total_page_cache: None,
+            max_mem_usage: None,
+            disk_io_time: None,
+            local_disk_space: None,
+            max_cpu_rate: None,
+            max_disk_io_tim: None,
+            cpi: None,
+            mapi: None,
+            sample_portion: None,
+            agg_type: None,
+            sampled_cpu_usage: None,
+        })
+    }
+
+    fn cpu_requests(analysis: &SlackAnalysis) -> Vec<(u64, f64)> {
+        analysis
+            .band(PriorityBand::Production)
+            .map(|(b, s)| (b / MINUTE, s.cpu_request))
+            .collect()
+    }
+
+    /// A task running from the start of the trace window until after its end.
+    fn whole_trace_task() -> Vec<Result<TraceEvent, Error>> {
+        vec![
+            task_event(0, TaskEventType::Submit),
+            task_event(0, TaskEventType::Schedule),
+            usage(TRACE_START_TIME, TRACE_START_TIME + 10 * MINUTE),
+            task_event(AFTER_TRACE_TIME, TaskEventType::Finish),
+        ]
+        .into_iter()
+        .map(Ok)
+        .collect()
+    }
+
+    #[test]
+    fn sentinel_times_are_clamped() {
+        let analysis = SlackAnalysis::from_events(whole_trace_task(), 5 * MINUTE).unwrap();
+        assert_eq!(cpu_requests(&analysis), vec![(10, 0.5), (15, 0.5)]);
+        let cpu_usage: Vec<f64> = analysis
+            .band(PriorityBand::Production)
+            .map(|(_, s)| s.cpu_usage)
+            .collect();
+        assert_eq!(cpu_usage, vec![0.25, 0.25]);
+    }
+
+    #[test]
+    fn trace_end_bounds_the_analysis() {
+        let end = TRACE_START_TIME + 5 * MINUTE / 2;
+        let analysis =
+            SlackAnalysis::from_events_until(whole_trace_task(), 5 * MINUTE, end).unwrap();
+        assert_eq!(cpu_requests(&analysis), vec![(10, 0.25)]);
+
+        // tasks still running at the end of the stream
+        let mut events = whole_trace_task();
+        events.pop();
+        let analysis = SlackAnalysis::from_events_until(events, 5 * MINUTE, end).unwrap();
+        assert_eq!(cpu_requests(&analysis), vec![(10, 0.25)]);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::common::TraceRecord;
+use crate::error::Error;
+use crate::iter::TraceFileIterator;
+use crate::schema::{self, Column, ColumnType, Value};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The SQL column type and constraint for a column.
+fn sql_type(column: &Column) -> &'static str {
+    match (column.column_type, column.nullable) {
+        (ColumnType::F64, true) => "REAL",
+        (ColumnType::F64, false) => "REAL NOT NULL",
+        (ColumnType::Str, true) => "TEXT",
+        (ColumnType::Str, false) => "TEXT NOT NULL",
+        (_, true) => "INTEGER",
+        (_, false) => "INTEGER NOT NULL",
+    }
+}
+
+fn sql_value(value: Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(b as i64),
+        Value::UInt(u) => SqlValue::Integer(u as i64),
+        Value::Int(i) => SqlValue::Integer(i),
+        Value::Float(f) => SqlValue::Real(f),
+        Value::Str(s) => SqlValue::Text(s),
+    }
+}
+
+/// A SQLite database holding one table per trace table, named after the table's directory and
+/// with the record struct's fields as columns. Enums are stored as their integer codes.
+pub struct Database {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if it does not exist.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let path = PathBuf::from(path);
+        let conn = Connection::open(&path).map_err(|e| Error::export(&path, e.to_string()))?;
+        Ok(Database { path, conn })
+    }
+
+    /// The path of the database file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The connection to the database, for querying it.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Loads all records of `T`'s table from the trace at `trace_path`. Returns the number of
+    /// records loaded.
+    pub fn load<T: TraceRecord + Serialize>(&mut self, trace_path: &str) -> Result<u64, Error> {
+        self.load_range::<T>(trace_path, 0, u64::MAX)
+    }
+
+    /// Loads the records of `T`'s table with timestamps in `[start, end)`, creating the table and
+    /// its indices if they do not exist yet. Records are appended, so loading overlapping ranges
+    /// duplicates rows.
+    ///
+    /// Each part file is loaded in its own transaction. If loading fails, the records of the
+    /// part files before the failing one remain in the table.
+    pub fn load_range<T: TraceRecord + Serialize>(
+        &mut self,
+        trace_path: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<u64, Error> {
+        let path = &self.path;
+        let sql = |e: rusqlite::Error| Error::export(path, e.to_string());
+        let columns = schema::columns::<T>().map_err(|e| Error::export(path, e.to_string()))?;
+
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|c| format!("{} {}", c.name, sql_type(c)))
+            .collect();
+        self.conn
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {} ({});",
+                T::TABLE,
+                definitions.join(", ")
+            ))
+            .map_err(sql)?;
+        let names: Vec<&str> = columns.iter().map(|c| c.name).collect();
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            T::TABLE,
+            names.join(", "),
+            vec!["?"; names.len()].join(", ")
+        );
+
+        let mut records =
+            TraceFileIterator::<T>::range(&Path::new(trace_path).join(T::TABLE), start, end)?;
+        let mut loaded = 0;
+        let mut pending = records.next();
+        while pending.is_some() {
+            let part = records.current_part();
+            let tx = self.conn.transaction().map_err(sql)?;
+            {
+                let mut statement = tx.prepare_cached(&insert).map_err(sql)?;
+                while let Some(record) = pending.take() {
+                    if records.current_part() != part {
+                        pending = Some(record);
+                        break;
+                    }
+                    let row =
+                        schema::to_row(&record?).map_err(|e| Error::export(path, e.to_string()))?;
+                    statement
+                        .execute(rusqlite::params_from_iter(row.into_iter().map(sql_value)))
+                        .map_err(sql)?;
+                    loaded += 1;
+                    pending = records.next();
+                }
+            }
+            tx.commit().map_err(sql)?;
+        }
+
+        // indices are built after the first load, which is much faster than maintaining them
+        // during it
+        let mut indices = vec![("time", columns[0].name.to_string())];
+        if names.contains(&"job_id") && names.contains(&"task_index") {
+            indices.push(("task", "job_id, task_index".to_string()));
+        }
+        if names.contains(&"machine_id") {
+            indices.push(("machine", "machine_id".to_string()));
+        }
+        for (suffix, indexed) in indices {
+            self.conn
+                .execute_batch(&format!(
+                    "CREATE INDEX IF NOT EXISTS {table}_{} ON {table} ({});",
+                    suffix,
+                    indexed,
+                    table = T::TABLE
+                ))
+                .map_err(sql)?;
+        }
+        Ok(loaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_events::TaskEvent;
+    use crate::testing::{scratch_dir, write_part};
+
+    fn task_event(time: u64, task_index: u64) -> String {
+        format!("{},,1,{},7,1,user1,1,9,0.5,,0.01,0", time, task_index)
+    }
+
+    fn fixture(bad_record: bool) -> (PathBuf, Database) {
+        let trace = scratch_dir();
+        let rows: Vec<String> = (0..3).map(|i| task_event(i * 100, i)).collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        write_part(&trace, "task_events", "part-00000-of-00002.csv", &rows);
+        let mut rows = vec![task_event(300, 3)];
+        if bad_record {
+            // no event type 99
+            rows.push("350,,1,4,7,99,user1,1,9,0.5,,0.01,0".to_string());
+        }
+        rows.push(task_event(400, 5));
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        write_part(&trace, "task_events", "part-00001-of-00002.csv", &rows);
+        let db = Database::open(trace.join("trace.db").to_str().unwrap()).unwrap();
+        (trace, db)
+    }
+
+    fn count(db: &Database, sql: &str) -> i64 {
+        db.connection().query_row(sql, [], |r| r.get(0)).unwrap()
+    }
+
+    #[test]
+    fn load_range_loads_the_range() {
+        let (trace, mut db) = fixture(false);
+        let loaded = db
+            .load_range::<TaskEvent>(trace.to_str().unwrap(), 100, 400)
+            .unwrap();
+        assert_eq!(loaded, 3);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM task_events"), 3);
+        assert_eq!(
+            count(&db, "SELECT MIN(time) + MAX(time) FROM task_events"),
+            400
+        );
+        assert_eq!(
+            count(
+                &db,
+                "SELECT COUNT(*) FROM task_events WHERE missing_info IS NULL \
+                 AND memory_request IS NULL AND event_type = 1 AND user = 'user1'"
+            ),
+            3
+        );
+
+        let mut statement = db
+            .connection()
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'index' ORDER BY name")
+            .unwrap();
+        let indices: Vec<String> = statement
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            indices,
+            vec![
+                "task_events_machine",
+                "task_events_task",
+                "task_events_time"
+            ]
+        );
+    }
+
+    #[test]
+    fn a_bad_record_rolls_back_its_part() {
+        let (trace, mut db) = fixture(true);
+        match db.load::<TaskEvent>(trace.to_str().unwrap()) {
+            Err(Error::Schema { path, .. }) => {
+                assert!(path.ends_with("part-00001-of-00002.csv"))
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("loaded a bad record"),
+        }
+        // the first part's records remain, and none of the second part's
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM task_events"), 3);
+        assert_eq!(count(&db, "SELECT MAX(time) FROM task_events"), 200);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::common::{SchedulingClass, AFTER_TRACE_TIME};
+use crate::error::Error;
+use crate::task_events::{TaskEvent, TaskEventIterator, TaskEventType};
+use std::collections::HashMap;
+use std::vec;
+
+/// One attempt at running a task: the period from a SUBMIT event to the terminal event that
+/// follows it. A task that is evicted, fails or is killed and then resubmitted has several.
+#[derive(Clone, Debug)]
+pub struct TaskAttempt {
+    pub job_id: u64,
+    pub task_index: u64,
+    /// The time of the attempt's SUBMIT event; `None` if the trace lacks it (e.g., because the
+    /// task was submitted before the trace started).
+    pub submit_time: Option<u64>,
+    /// The time at which the attempt was scheduled, if it ever was.
+    pub schedule_time: Option<u64>,
+    /// The time of the attempt's terminal event; `None` if it had not terminated by the end of
+    /// the events, and `AFTER_TRACE_TIME` if it terminated after the trace window.
+    pub end_time: Option<u64>,
+    pub machine_id: Option<u64>,
+    pub priority: u8,
+    pub scheduling_class: Option<SchedulingClass>,
+    /// The requests in effect at the end of the attempt, i.e., including any updates.
+    pub cpu_request: Option<f64>,
+    pub memory_request: Option<f64>,
+    pub disk_space_request: Option<f64>,
+    pub terminal_event: Option<TaskEventType>,
+}
+
+impl TaskAttempt {
+    fn new(e: &TaskEvent) -> Self {
+        TaskAttempt {
+            job_id: e.job_id,
+            task_index: e.task_index,
+            submit_time: None,
+            schedule_time: None,
+            end_time: None,
+            machine_id: None,
+            priority: e.priority,
+            scheduling_class: None,
+            cpu_request: None,
+            memory_request: None,
+            disk_space_request: None,
+            terminal_event: None,
+        }
+    }
+
+    /// The time the attempt spent waiting to be scheduled (SUBMIT to SCHEDULE).
+    pub fn queueing_delay(&self) -> Option<u64> {
+        self.schedule_time?.checked_sub(self.submit_time?)
+    }
+
+    /// The time the attempt spent on a machine (SCHEDULE to its terminal event). `None` if the
+    /// attempt terminated after the trace window, since the time it ran for is then unknown.
+    pub fn runtime(&self) -> Option<u64> {
+        let end_time = self.end_time.filter(|t| *t < AFTER_TRACE_TIME)?;
+        end_time.checked_sub(self.schedule_time?)
+    }
+
+    fn update(&mut self, e: &TaskEvent) {
+        self.priority = e.priority;
+        self.scheduling_class = e.scheduling_class.or(self.scheduling_class);
+        self.cpu_request = e.cpu_request.or(self.cpu_request);
+        self.memory_request = e.memory_request.or(self.memory_request);
+        self.disk_space_request = e.disk_space_request.or(self.disk_space_request);
+        self.machine_id = e.machine_id.or(self.machine_id);
+    }
+}
+
+/// Extracts `TaskAttempt`s from a stream of task events in time order.
+///
+/// Attempts are yielded when they terminate; those still live at the end of the events follow
+/// in `(job_id, task_index)` order.
+pub struct TaskAttemptIterator<I> {
+    events: I,
+    live: HashMap<(u64, u64), TaskAttempt>,
+    unterminated: Option<vec::IntoIter<TaskAttempt>>,
+}
+
+impl TaskAttemptIterator<TaskEventIterator> {
+    /// Extracts the attempts of all tasks in the trace at `trace_path`.
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        Ok(Self::from_events(TaskEventIterator::new(trace_path)?))
+    }
+}
+
+impl<I> TaskAttemptIterator<I>
+where
+    I: Iterator<Item = Result<TaskEvent, Error>>,
+{
+    pub fn from_events(events: I) -> Self {
+        TaskAttemptIterator {
+            events,
+            live: HashMap::new(),
+            unterminated: None,
+        }
+    }
+
+    /// Applies an event, returning the attempt it completes, if any.
+    fn apply(&mut self, e: &TaskEvent) -> Option<TaskAttempt> {
+        let key = (e.job_id, e.task_index);
+        match e.event_type {
+            TaskEventType::Submit => {
+                let mut attempt = TaskAttempt::new(e);
+                attempt.submit_time = Some(e.time);
+                attempt.update(e);
+                // a SUBMIT for a live attempt starts a new one; the old one never terminated
+                self.live.insert(key, attempt)
+            }
+            TaskEventType::Schedule
+            | TaskEventType::UpdatePending
+            | TaskEventType::UpdateRunning => {
+                let attempt = self.live.entry(key).or_insert_with(|| TaskAttempt::new(e));
+                if e.event_type == TaskEventType::Schedule && attempt.schedule_time.is_none() {
+                    attempt.schedule_time = Some(e.time);
+                }
+                attempt.update(e);
+                None
+            }
+            TaskEventType::Evict
+            | TaskEventType::Fail
+            | TaskEventType::Finish
+            | TaskEventType::Kill
+            | TaskEventType::Lost => {
+                let mut attempt = self
+                    .live
+                    .remove(&key)
+                    .unwrap_or_else(|| TaskAttempt::new(e));
+                attempt.update(e);
+                attempt.end_time = Some(e.time);
+                attempt.terminal_event = Some(e.event_type);
+                Some(attempt)
+            }
+        }
+    }
+}
+
+impl<I> Iterator for TaskAttemptIterator<I>
+where
+    I: Iterator<Item = Result<TaskEvent, Error>>,
+{
+    type Item = Result<TaskAttempt, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(rest) = self.unterminated.as_mut() {
+            return rest.next().map(Ok);
+        }
+        while let Some(r) = self.events.next() {
+            match r {
+                Ok(e) => {
+                    if let Some(attempt) = self.apply(&e) {
+                        return Some(Ok(attempt));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        let mut rest: Vec<TaskAttempt> = self.live.drain().map(|(_, a)| a).collect();
+        rest.sort_by_key(|a| (a.job_id, a.task_index));
+        self.unterminated = Some(rest.into_iter());
+        self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse;
+
+    fn event(time: u64, job_id: u64, task_index: u64, event_type: u8, cpu: &str) -> TaskEvent {
+        parse(&format!(
+            "{},,{},{},7,{},,1,0,{},,,",
+            time, job_id, task_index, event_type, cpu
+        ))
+    }
+
+    fn attempts(events: Vec<TaskEvent>) -> Vec<TaskAttempt> {
+        TaskAttemptIterator::from_events(events.into_iter().map(Ok))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn evicted_tasks_have_several_attempts() {
+        let found = attempts(vec![
+            event(0, 1, 0, 0, "0.5"),
+            event(10, 1, 0, 1, ""),
+            event(50, 1, 0, 8, "0.25"),
+            event(100, 1, 0, 2, ""),
+            event(120, 1, 0, 0, "0.25"),
+            event(150, 1, 0, 1, ""),
+            event(400, 1, 0, 4, ""),
+        ]);
+        assert_eq!(found.len(), 2);
+        let (evicted, finished) = (&found[0], &found[1]);
+        assert_eq!(evicted.terminal_event, Some(TaskEventType::Evict));
+        assert_eq!(evicted.queueing_delay(), Some(10));
+        assert_eq!(evicted.runtime(), Some(90));
+        assert_eq!(evicted.machine_id, Some(7));
+        assert_eq!(evicted.cpu_request, Some(0.25));
+        assert_eq!(finished.terminal_event, Some(TaskEventType::Finish));
+        assert_eq!(finished.submit_time, Some(120));
+        assert_eq!(finished.queueing_delay(), Some(30));
+        assert_eq!(finished.runtime(), Some(250));
+    }
+
+    #[test]
+    fn resubmitting_a_live_attempt_ends_it_unterminated() {
+        let found = attempts(vec![
+            event(0, 1, 0, 0, ""),
+            event(10, 1, 0, 1, ""),
+            event(20, 1, 0, 0, ""),
+            event(30, 1, 0, 1, ""),
+            event(40, 1, 0, 5, ""),
+        ]);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].submit_time, Some(0));
+        assert_eq!(found[0].schedule_time, Some(10));
+        assert_eq!(found[0].end_time, None);
+        assert_eq!(found[0].terminal_event, None);
+        assert_eq!(found[0].runtime(), None);
+        assert_eq!(found[1].submit_time, Some(20));
+        assert_eq!(found[1].terminal_event, Some(TaskEventType::Kill));
+        assert_eq!(found[1].runtime(), Some(10));
+    }
+
+    #[test]
+    fn attempts_ending_after_the_trace_have_no_runtime() {
+        let found = attempts(vec![
+            event(600_000_000, 1, 0, 0, ""),
+            event(600_000_010, 1, 0, 1, ""),
+            event(AFTER_TRACE_TIME, 1, 0, 4, ""),
+        ]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].end_time, Some(AFTER_TRACE_TIME));
+        assert_eq!(found[0].terminal_event, Some(TaskEventType::Finish));
+        assert_eq!(found[0].queueing_delay(), Some(10));
+        assert_eq!(found[0].runtime(), None);
+    }
+
+    #[test]
+    fn unterminated_attempts_come_last_in_task_order() {
+        let found = attempts(vec![
+            event(0, 2, 1, 0, ""),
+            event(0, 2, 0, 0, ""),
+            event(0, 1, 3, 0, ""),
+            event(5, 1, 3, 1, ""),
+            event(10, 3, 0, 0, ""),
+            event(20, 3, 0, 3, ""),
+            // a task whose SUBMIT precedes the events
+            event(30, 1, 5, 1, ""),
+        ]);
+        let keys: Vec<_> = found
+            .iter()
+            .map(|a| (a.job_id, a.task_index, a.terminal_event))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                (3, 0, Some(TaskEventType::Fail)),
+                (1, 3, None),
+                (1, 5, None),
+                (2, 0, None),
+                (2, 1, None),
+            ]
+        );
+        assert_eq!(found[1].queueing_delay(), Some(5));
+        assert_eq!(found[2].submit_time, None);
+        assert_eq!(found[2].queueing_delay(), None);
+        assert!(found[1..].iter().all(|a| a.end_time.is_none()));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::common::{AttributeValue, TraceRecord};
+use crate::error::Error;
+use crate::iter::{TraceFileIterator, TracePosition};
+use std::path::Path;
+
+// 1,time,INTEGER,YES
+// 2,job ID,INTEGER,YES
+// 3,task index,INTEGER,YES
+// 4,comparison operator,INTEGER,YES
+// 5,attribute name,STRING_HASH,YES
+// 6,attribute value,STRING_HASH_OR_INTEGER,NO
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TaskConstraint {
+    pub time: u64,
+    pub job_id: u64,
+    pub task_index: u64,
+    pub comparison_operator: ConstraintOperator,
+    pub attribute_name: String,
+    pub attribute_value: Option<AttributeValue>,
+}
+
+impl TraceRecord for TaskConstraint {
+    const TABLE: &'static str = "task_constraints";
+
+    fn time(&self) -> u64 {
+        self.time
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize_repr, Serialize_repr, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ConstraintOperator {
+    /// EQUAL (0): the machine's attribute value must equal the constraint's value.
+    Equal = 0,
+    /// NOT_EQUAL (1): the machine's attribute value must differ from the constraint's value.
+    NotEqual = 1,
+    /// LESS_THAN (2): the machine's attribute value must be an integer less than the constraint's.
+    LessThan = 2,
+    /// GREATER_THAN (3): the machine's attribute value must be an integer greater than the
+    /// constraint's.
+    GreaterThan = 3,
+}
+
+impl TaskConstraint {
+    /// Whether a machine's value for the constrained attribute satisfies the constraint. A
+    /// machine lacking the attribute, or having it without a value, is taken to have an empty
+    /// value: it matches an EQUAL constraint with an empty value, and never satisfies LESS_THAN or
+    /// GREATER_THAN, which only compare integers.
+    pub fn is_satisfied_by(&self, machine_value: Option<&AttributeValue>) -> bool {
+        use self::ConstraintOperator::*;
+        use crate::common::AttributeValue::Integer;
+
+        match (
+            self.comparison_operator,
+            machine_value,
+            self.attribute_value.as_ref(),
+        ) {
+            (Equal, m, c) => m == c,
+            (NotEqual, m, c) => m != c,
+            (LessThan, Some(Integer(m)), Some(Integer(c))) => m < c,
+            (GreaterThan, Some(Integer(m)), Some(Integer(c))) => m > c,
+            _ => false,
+        }
+    }
+}
+
+pub struct TaskConstraintIterator {
+    file_iter: TraceFileIterator<TaskConstraint>,
+}
+
+impl TaskConstraintIterator {
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(TaskConstraint::TABLE);
+        Ok(TaskConstraintIterator {
+            file_iter: TraceFileIterator::new(&fp)?,
+        })
+    }
+
+    /// Iterates over the records with timestamps in `[start, end)`, skipping part files that
+    /// end before `start`.
+    pub fn range(trace_path: &str, start: u64, end: u64) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(TaskConstraint::TABLE);
+        Ok(TaskConstraintIterator {
+            file_iter: TraceFileIterator::range(&fp, start, end)?,
+        })
+    }
+
+    /// The part file and record offset the iterator has reached.
+    pub fn current_position(&self) -> TracePosition {
+        self.file_iter.current_position()
+    }
+}
+
+impl Iterator for TaskConstraintIterator {
+    type Item = Result<TaskConstraint, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.file_iter.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse;
+
+    #[test]
+    fn constraints_compare_machine_values() {
+        let hash = |s: &str| Some(AttributeValue::Hash(s.to_string()));
+        let int = |i| Some(AttributeValue::Integer(i));
+        // operator, constraint value, machine value, satisfied
+        let cases = [
+            (0, "r1", hash("r1"), true),
+            (0, "r1", hash("r2"), false),
+            (0, "r1", None, false),
+            (0, "", None, true),
+            (0, "", hash("r1"), false),
+            (0, "5", int(5), true),
+            (0, "5", hash("x5"), false),
+            (1, "r1", hash("r1"), false),
+            (1, "r1", None, true),
+            (1, "", None, false),
+            (1, "5", int(6), true),
+            (2, "5", int(4), true),
+            (2, "5", int(5), false),
+            (2, "5", None, false),
+            (2, "5", hash("x4"), false),
+            (2, "x5", int(4), false),
+            (2, "", int(4), false),
+            (3, "5", int(6), true),
+            (3, "5", int(5), false),
+            (3, "-1", int(0), true),
+            (3, "5", None, false),
+            (3, "5", hash("x6"), false),
+            (3, "", None, false),
+        ];
+        for (op, value, machine_value, satisfied) in cases.iter() {
+            let c: TaskConstraint = parse(&format!("0,1,0,{},attr,{}", op, value));
+            assert_eq!(
+                c.is_satisfied_by(machine_value.as_ref()),
+                *satisfied,
+                "{:?} {:?} against {:?}",
+                c.comparison_operator,
+                c.attribute_value,
+                machine_value
+            );
+        }
+    }
+}
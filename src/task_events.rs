@@ -1,7 +1,7 @@
-use crate::common::{MissingInfo, SchedulingClass};
-use crate::iter::TraceFileIterator;
-
-pub(crate) static TASK_EVENT_FILE_COUNT: usize = 500;
+use crate::common::{MissingInfo, SchedulingClass, TraceRecord};
+use crate::error::Error;
+use crate::iter::{TraceFileIterator, TracePosition};
+use std::path::Path;
 
 // 1,time,INTEGER,YES
 // 2,missing info,INTEGER,NO
@@ -16,7 +16,7 @@ pub(crate) static TASK_EVENT_FILE_COUNT: usize = 500;
 // 11,memory request,FLOAT,NO
 // 12,disk space request,FLOAT,NO
 // 13,different machines restriction,BOOLEAN,NO
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TaskEvent {
     pub time: u64,
     pub missing_info: Option<MissingInfo>,
@@ -33,7 +33,15 @@ pub struct TaskEvent {
     pub different_machines_restrict: Option<u8>, // bool
 }
 
-#[derive(Debug, Deserialize_repr, PartialEq)]
+impl TraceRecord for TaskEvent {
+    const TABLE: &'static str = "task_events";
+
+    fn time(&self) -> u64 {
+        self.time
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize_repr, Serialize_repr, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum TaskEventType {
     /// SUBMIT (0): A task or job became eligible for scheduling.
@@ -71,16 +79,30 @@ pub struct TaskEventIterator {
 }
 
 impl TaskEventIterator {
-    pub fn new(trace_path: &str) -> Self {
-        let fp = format!("{}/task_events/", trace_path);
-        TaskEventIterator {
-            file_iter: TraceFileIterator::new(&fp, TASK_EVENT_FILE_COUNT),
-        }
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(TaskEvent::TABLE);
+        Ok(TaskEventIterator {
+            file_iter: TraceFileIterator::new(&fp)?,
+        })
+    }
+
+    /// Iterates over the records with timestamps in `[start, end)`, skipping part files that
+    /// end before `start`.
+    pub fn range(trace_path: &str, start: u64, end: u64) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(TaskEvent::TABLE);
+        Ok(TaskEventIterator {
+            file_iter: TraceFileIterator::range(&fp, start, end)?,
+        })
+    }
+
+    /// The part file and record offset the iterator has reached.
+    pub fn current_position(&self) -> TracePosition {
+        self.file_iter.current_position()
     }
 }
 
 impl Iterator for TaskEventIterator {
-    type Item = Result<TaskEvent, csv::Error>;
+    type Item = Result<TaskEvent, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.file_iter.next()
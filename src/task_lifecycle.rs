@@ -0,0 +1,272 @@
+use crate::common::MissingInfo;
+use crate::error::Error;
+use crate::task_events::{TaskEvent, TaskEventType};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The states of a task in the trace's task lifecycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TaskLifecycleState {
+    /// The task has not been submitted yet.
+    Unsubmitted,
+    /// The task has been submitted and is waiting to be scheduled.
+    Pending,
+    /// The task has been scheduled on a machine.
+    Running,
+    /// The task has terminated; it may be resubmitted later.
+    Dead,
+}
+
+impl TaskLifecycleState {
+    /// The state that a task moves to on an event, if the transition is legal from this state.
+    pub fn next(self, event_type: TaskEventType) -> Option<TaskLifecycleState> {
+        use self::TaskLifecycleState::*;
+        use crate::task_events::TaskEventType::*;
+
+        match (self, event_type) {
+            (Unsubmitted, Submit) | (Dead, Submit) => Some(Pending),
+            (Pending, UpdatePending) => Some(Pending),
+            (Pending, Schedule) => Some(Running),
+            (Pending, Fail) | (Pending, Kill) | (Pending, Lost) => Some(Dead),
+            (Running, UpdateRunning) => Some(Running),
+            (Running, Evict)
+            | (Running, Fail)
+            | (Running, Finish)
+            | (Running, Kill)
+            | (Running, Lost) => Some(Dead),
+            _ => None,
+        }
+    }
+
+    /// The state that an event implies a task is in afterwards, regardless of its prior state.
+    fn implied_by(event_type: TaskEventType) -> TaskLifecycleState {
+        match event_type {
+            TaskEventType::Submit | TaskEventType::UpdatePending => TaskLifecycleState::Pending,
+            TaskEventType::Schedule | TaskEventType::UpdateRunning => TaskLifecycleState::Running,
+            TaskEventType::Evict
+            | TaskEventType::Fail
+            | TaskEventType::Finish
+            | TaskEventType::Kill
+            | TaskEventType::Lost => TaskLifecycleState::Dead,
+        }
+    }
+}
+
+/// A legal transition taken by a task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub from: TaskLifecycleState,
+    pub to: TaskLifecycleState,
+}
+
+impl Transition {
+    /// Whether the transition resubmits a task that had previously terminated.
+    pub fn is_resubmission(&self) -> bool {
+        self.from == TaskLifecycleState::Dead && self.to == TaskLifecycleState::Pending
+    }
+}
+
+/// An event that the task lifecycle does not allow in the task's current state (e.g., a SCHEDULE
+/// for a task that is already running).
+#[derive(Clone, Debug)]
+pub struct IllegalTransition {
+    /// The state the task was in before the event.
+    pub from: TaskLifecycleState,
+    /// The offending event.
+    pub event: Box<TaskEvent>,
+}
+
+impl IllegalTransition {
+    /// The trace's annotation of the offending event, if it was synthesized.
+    pub fn missing_info(&self) -> Option<MissingInfo> {
+        self.event.missing_info
+    }
+}
+
+impl fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "task {}:{} received {:?} at {} while {:?}",
+            self.event.job_id,
+            self.event.task_index,
+            self.event.event_type,
+            self.event.time,
+            self.from
+        )?;
+        if let Some(mi) = self.event.missing_info {
+            write!(f, " (missing info: {:?})", mi)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TaskEntry {
+    state: TaskLifecycleState,
+    submissions: u32,
+}
+
+/// Tracks the lifecycle state of every task, keyed by `(job_id, task_index)`, and validates the
+/// transitions made by each task event.
+#[derive(Debug, Default)]
+pub struct TaskStateMachine {
+    tasks: HashMap<(u64, u64), TaskEntry>,
+}
+
+impl TaskStateMachine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current state of a task.
+    pub fn state(&self, job_id: u64, task_index: u64) -> TaskLifecycleState {
+        self.tasks
+            .get(&(job_id, task_index))
+            .map_or(TaskLifecycleState::Unsubmitted, |t| t.state)
+    }
+
+    /// The number of times a task has been submitted so far.
+    pub fn submissions(&self, job_id: u64, task_index: u64) -> u32 {
+        self.tasks
+            .get(&(job_id, task_index))
+            .map_or(0, |t| t.submissions)
+    }
+
+    /// Moves the event's task to its next state. If the transition is illegal, the task is still
+    /// moved to the state the event implies, so that a single bad record does not cause errors
+    /// for all of the task's later events.
+    pub fn apply(&mut self, event: &TaskEvent) -> Result<Transition, IllegalTransition> {
+        let entry = self
+            .tasks
+            .entry((event.job_id, event.task_index))
+            .or_insert(TaskEntry {
+                state: TaskLifecycleState::Unsubmitted,
+                submissions: 0,
+            });
+        let from = entry.state;
+        if event.event_type == TaskEventType::Submit {
+            entry.submissions += 1;
+        }
+        match from.next(event.event_type) {
+            Some(to) => {
+                entry.state = to;
+                Ok(Transition { from, to })
+            }
+            None => {
+                entry.state = TaskLifecycleState::implied_by(event.event_type);
+                Err(IllegalTransition {
+                    from,
+                    event: Box::new(event.clone()),
+                })
+            }
+        }
+    }
+}
+
+/// Runs task events through a `TaskStateMachine` and collects the illegal transitions found.
+pub fn illegal_transitions<I>(events: I) -> Result<Vec<IllegalTransition>, Error>
+where
+    I: IntoIterator<Item = Result<TaskEvent, Error>>,
+{
+    let mut sm = TaskStateMachine::new();
+    let mut illegal = Vec::new();
+    for ev in events {
+        if let Err(it) = sm.apply(&ev?) {
+            illegal.push(it);
+        }
+    }
+    Ok(illegal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TaskLifecycleState::*;
+    use super::*;
+    use crate::testing::parse;
+
+    /// A task event for task 0 of job 1, with an optional missing-info annotation.
+    fn event(time: u64, event_type: u8, missing_info: &str) -> TaskEvent {
+        parse(&format!(
+            "{},{},1,0,,{},,,0,,,,",
+            time, missing_info, event_type
+        ))
+    }
+
+    #[test]
+    fn legal_transitions_and_resubmission() {
+        let mut sm = TaskStateMachine::new();
+        assert_eq!(sm.state(1, 0), Unsubmitted);
+        let transitions: Vec<_> = [0, 1, 2, 0, 1, 4]
+            .iter()
+            .enumerate()
+            .map(|(t, &et)| sm.apply(&event(t as u64, et, "")).unwrap())
+            .collect();
+        let states: Vec<_> = transitions.iter().map(|t| (t.from, t.to)).collect();
+        assert_eq!(
+            states,
+            vec![
+                (Unsubmitted, Pending),
+                (Pending, Running),
+                (Running, Dead),
+                (Dead, Pending),
+                (Pending, Running),
+                (Running, Dead),
+            ]
+        );
+        let resubmissions: Vec<_> = transitions.iter().map(|t| t.is_resubmission()).collect();
+        assert_eq!(resubmissions, vec![false, false, false, true, false, false]);
+        assert_eq!(sm.state(1, 0), Dead);
+        assert_eq!(sm.submissions(1, 0), 2);
+        assert_eq!(sm.state(1, 1), Unsubmitted);
+        assert_eq!(sm.submissions(1, 1), 0);
+    }
+
+    #[test]
+    fn illegal_transitions_move_to_the_implied_state() {
+        let mut sm = TaskStateMachine::new();
+        sm.apply(&event(0, 0, "")).unwrap();
+        sm.apply(&event(1, 1, "")).unwrap();
+
+        // a second SCHEDULE while running
+        let it = sm.apply(&event(2, 1, "0")).unwrap_err();
+        assert_eq!(it.from, Running);
+        assert_eq!(it.event.time, 2);
+        assert_eq!(it.event.event_type, TaskEventType::Schedule);
+        assert_eq!(
+            it.missing_info(),
+            Some(MissingInfo::SnapshotButNoTransition)
+        );
+        assert_eq!(sm.state(1, 0), Running);
+
+        // a SUBMIT while running restarts the task as pending
+        let it = sm.apply(&event(3, 0, "")).unwrap_err();
+        assert_eq!(it.from, Running);
+        assert_eq!(it.missing_info(), None);
+        assert_eq!(sm.state(1, 0), Pending);
+        assert_eq!(sm.submissions(1, 0), 2);
+
+        // a FINISH while pending kills it, after which it can be resubmitted legally
+        assert_eq!(sm.apply(&event(4, 4, "")).unwrap_err().from, Pending);
+        assert_eq!(sm.state(1, 0), Dead);
+        assert!(sm.apply(&event(5, 0, "")).unwrap().is_resubmission());
+    }
+
+    #[test]
+    fn illegal_transitions_are_collected() {
+        let events = vec![
+            event(0, 1, ""),
+            event(1, 0, ""),
+            event(2, 1, ""),
+            event(3, 8, ""),
+            event(4, 7, ""),
+        ];
+        let illegal = illegal_transitions(events.into_iter().map(Ok)).unwrap();
+        let found: Vec<_> = illegal.iter().map(|it| (it.event.time, it.from)).collect();
+        assert_eq!(found, vec![(0, Unsubmitted), (1, Running), (4, Running)]);
+        assert_eq!(
+            illegal[1].to_string(),
+            "task 1:0 received Submit at 1 while Running"
+        );
+    }
+}
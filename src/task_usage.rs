@@ -1,6 +1,7 @@
-use crate::iter::TraceFileIterator;
-
-pub(crate) static TASK_USAGE_FILE_COUNT: usize = 500;
+use crate::common::TraceRecord;
+use crate::error::Error;
+use crate::iter::{TraceFileIterator, TracePosition};
+use std::path::Path;
 
 // 1,start time,INTEGER,YES
 // 2,end time,INTEGER,YES
@@ -22,7 +23,7 @@ pub(crate) static TASK_USAGE_FILE_COUNT: usize = 500;
 // 18,sample portion,FLOAT,NO
 // 19,aggregation type,BOOLEAN,NO
 // 20,sampled CPU usage,FLOAT,NO
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TaskUsageRecord {
     pub start_time: u64,
     pub end_time: u64,
@@ -46,21 +47,43 @@ pub struct TaskUsageRecord {
     pub sampled_cpu_usage: Option<f64>,
 }
 
+impl TraceRecord for TaskUsageRecord {
+    const TABLE: &'static str = "task_usage";
+
+    fn time(&self) -> u64 {
+        self.start_time
+    }
+}
+
 pub struct TaskUsageIterator {
     file_iter: TraceFileIterator<TaskUsageRecord>,
 }
 
 impl TaskUsageIterator {
-    pub fn new(trace_path: &str) -> Self {
-        let fp = format!("{}/task_usage/", trace_path);
-        TaskUsageIterator {
-            file_iter: TraceFileIterator::new(&fp, TASK_USAGE_FILE_COUNT),
-        }
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(TaskUsageRecord::TABLE);
+        Ok(TaskUsageIterator {
+            file_iter: TraceFileIterator::new(&fp)?,
+        })
+    }
+
+    /// Iterates over the records with start times in `[start, end)`, skipping part files that
+    /// end before `start`.
+    pub fn range(trace_path: &str, start: u64, end: u64) -> Result<Self, Error> {
+        let fp = Path::new(trace_path).join(TaskUsageRecord::TABLE);
+        Ok(TaskUsageIterator {
+            file_iter: TraceFileIterator::range(&fp, start, end)?,
+        })
+    }
+
+    /// The part file and record offset the iterator has reached.
+    pub fn current_position(&self) -> TracePosition {
+        self.file_iter.current_position()
     }
 }
 
 impl Iterator for TaskUsageIterator {
-    type Item = Result<TaskUsageRecord, csv::Error>;
+    type Item = Result<TaskUsageRecord, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.file_iter.next()
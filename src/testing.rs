@@ -0,0 +1,55 @@
+// helpers shared by the unit tests
+
+use crate::common::TraceRecord;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A new, empty directory for a test's files.
+pub(crate) fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "gctu-test-{}-{}",
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes a part file of `table` in the trace at `trace`, one CSV line per row, gzipped if its
+/// name ends in `.gz`.
+pub(crate) fn write_part(trace: &Path, table: &str, name: &str, rows: &[&str]) {
+    let dir = trace.join(table);
+    fs::create_dir_all(&dir).unwrap();
+    let mut contents = String::new();
+    for row in rows {
+        contents.push_str(row);
+        contents.push('\n');
+    }
+    let file = File::create(dir.join(name)).unwrap();
+    if name.ends_with(".gz") {
+        let mut gz = GzEncoder::new(file, Compression::default());
+        gz.write_all(contents.as_bytes()).unwrap();
+        gz.finish().unwrap();
+    } else {
+        let mut file = file;
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+}
+
+/// Parses a record from a line of its table's CSV.
+pub(crate) fn parse<T: TraceRecord>(line: &str) -> T {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(line.as_bytes())
+        .deserialize()
+        .next()
+        .expect("a record")
+        .unwrap()
+}
@@ -0,0 +1,192 @@
+use crate::cluster_state::ClusterReplay;
+use crate::common::SchedulingClass;
+use crate::error::Error;
+use crate::merge::Table;
+use crate::task_usage::{TaskUsageIterator, TaskUsageRecord};
+
+/// A task usage record annotated with the task's request and the capacity of its machine at the
+/// start of the measurement period. Fields are `None` where the trace's events do not tell (e.g.,
+/// for tasks submitted before the trace started without a later update).
+#[derive(Clone, Debug)]
+pub struct AnnotatedUsage {
+    pub usage: TaskUsageRecord,
+    pub priority: Option<u8>,
+    pub scheduling_class: Option<SchedulingClass>,
+    pub cpu_request: Option<f64>,
+    pub memory_request: Option<f64>,
+    pub disk_space_request: Option<f64>,
+    pub machine_cpus: Option<f64>,
+    pub machine_memory: Option<f64>,
+}
+
+fn ratio(num: Option<f64>, denom: Option<f64>) -> Option<f64> {
+    match (num, denom) {
+        (Some(n), Some(d)) if d > 0.0 => Some(n / d),
+        _ => None,
+    }
+}
+
+impl AnnotatedUsage {
+    /// The task's mean CPU rate as a fraction of its CPU request.
+    pub fn cpu_usage_to_request(&self) -> Option<f64> {
+        ratio(self.usage.cpu_rate, self.cpu_request)
+    }
+
+    /// The task's canonical memory usage as a fraction of its memory request.
+    pub fn memory_usage_to_request(&self) -> Option<f64> {
+        ratio(self.usage.canonical_mem_usage, self.memory_request)
+    }
+
+    /// The task's mean CPU rate as a fraction of its machine's CPU capacity.
+    pub fn cpu_usage_to_capacity(&self) -> Option<f64> {
+        ratio(self.usage.cpu_rate, self.machine_cpus)
+    }
+
+    /// The task's canonical memory usage as a fraction of its machine's memory capacity.
+    pub fn memory_usage_to_capacity(&self) -> Option<f64> {
+        ratio(self.usage.canonical_mem_usage, self.machine_memory)
+    }
+}
+
+/// The tables replayed for the join; the job and machine attribute tables add nothing to it.
+const JOIN_TABLES: [Table; 2] = [Table::MachineEvents, Table::TaskEvents];
+
+/// Joins task usage records with the task and machine state replayed from the machine and task
+/// event tables, in a single pass over the trace.
+pub struct UsageJoin {
+    replay: ClusterReplay,
+    usage: TaskUsageIterator,
+}
+
+impl UsageJoin {
+    /// Annotates all task usage records in the trace at `trace_path`.
+    pub fn new(trace_path: &str) -> Result<Self, Error> {
+        Ok(UsageJoin {
+            replay: ClusterReplay::with_tables(trace_path, &JOIN_TABLES)?,
+            usage: TaskUsageIterator::new(trace_path)?,
+        })
+    }
+
+    /// Annotates the task usage records with start times in `[start, end)`. The events before
+    /// `start` are still replayed to reconstruct the state at `start`.
+    pub fn range(trace_path: &str, start: u64, end: u64) -> Result<Self, Error> {
+        Ok(UsageJoin {
+            replay: ClusterReplay::with_tables(trace_path, &JOIN_TABLES)?,
+            usage: TaskUsageIterator::range(trace_path, start, end)?,
+        })
+    }
+
+    fn annotate(&mut self, usage: TaskUsageRecord) -> Result<AnnotatedUsage, Error> {
+        let state = self.replay.advance_to(usage.start_time)?;
+        let task = state.tasks.get(&(usage.job_id, usage.task_index));
+        let machine = state.machines.get(&usage.machine_id);
+        Ok(AnnotatedUsage {
+            priority: task.map(|t| t.priority),
+            scheduling_class: task.and_then(|t| t.scheduling_class),
+            cpu_request: task.and_then(|t| t.cpu_request),
+            memory_request: task.and_then(|t| t.memory_request),
+            disk_space_request: task.and_then(|t| t.disk_space_request),
+            machine_cpus: machine.and_then(|m| m.cpus),
+            machine_memory: machine.and_then(|m| m.memory),
+            usage,
+        })
+    }
+}
+
+impl Iterator for UsageJoin {
+    type Item = Result<AnnotatedUsage, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.usage.next()?.and_then(|u| self.annotate(u)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{scratch_dir, write_part};
+
+    fn usage(start_time: u64, cpu_rate: f64) -> String {
+        format!(
+            "{},{},1,0,1,{},0.25,,,,,,,,,,,,,",
+            start_time,
+            start_time + 100,
+            cpu_rate
+        )
+    }
+
+    /// A trace with no job_events or machine_attributes tables.
+    fn fixture() -> String {
+        let trace = scratch_dir();
+        let part = "part-00000-of-00001.csv";
+        write_part(
+            &trace,
+            "machine_events",
+            part,
+            &["0,1,0,p1,0.5,0.5", "300,1,2,,1,"],
+        );
+        write_part(
+            &trace,
+            "task_events",
+            part,
+            &[
+                "100,,1,0,,0,u1,2,9,0.25,0.5,,0",
+                "150,,1,0,1,1,u1,2,9,,,,0",
+                "200,,1,0,1,8,u1,2,10,0.5,,,0",
+            ],
+        );
+        let (a, b, c) = (usage(150, 0.25), usage(200, 0.25), usage(300, 0.5));
+        write_part(&trace, "task_usage", part, &[&a, &b, &c]);
+        trace.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn usage_is_joined_to_the_state_at_its_start() {
+        let trace = fixture();
+        let joined: Vec<AnnotatedUsage> = UsageJoin::new(&trace)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let requests: Vec<_> = joined
+            .iter()
+            .map(|a| (a.priority, a.cpu_request, a.memory_request))
+            .collect();
+        assert_eq!(
+            requests,
+            vec![
+                (Some(9), Some(0.25), Some(0.5)),
+                (Some(10), Some(0.5), Some(0.5)),
+                (Some(10), Some(0.5), Some(0.5)),
+            ]
+        );
+        let capacities: Vec<_> = joined
+            .iter()
+            .map(|a| (a.machine_cpus, a.machine_memory))
+            .collect();
+        assert_eq!(
+            capacities,
+            vec![
+                (Some(0.5), Some(0.5)),
+                (Some(0.5), Some(0.5)),
+                (Some(1.0), Some(0.5)),
+            ]
+        );
+        assert_eq!(joined[0].cpu_usage_to_request(), Some(1.0));
+        assert_eq!(joined[1].cpu_usage_to_request(), Some(0.5));
+        assert_eq!(joined[2].cpu_usage_to_capacity(), Some(0.5));
+        assert_eq!(joined[2].memory_usage_to_request(), Some(0.5));
+    }
+
+    #[test]
+    fn range_replays_the_events_before_it() {
+        let trace = fixture();
+        let joined: Vec<AnnotatedUsage> = UsageJoin::range(&trace, 200, 300)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[0].usage.start_time, 200);
+        assert_eq!(joined[0].cpu_request, Some(0.5));
+        assert_eq!(joined[0].machine_cpus, Some(0.5));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::error::Error;
+use crate::par::par_map_reduce_parts;
+use crate::task_usage::TaskUsageRecord;
+use std::collections::{BTreeMap, HashMap};
+
+/// The length of the trace's usage measurement periods (5 minutes), in microseconds.
+pub static MEASUREMENT_PERIOD: u64 = 300_000_000;
+
+/// The buckets of width `bucket_width` that overlap `[start, end)`, as `(bucket start time,
+/// fraction of the bucket covered)` pairs.
+pub(crate) fn bucket_overlaps(
+    start: u64,
+    end: u64,
+    bucket_width: u64,
+) -> impl Iterator<Item = (u64, f64)> {
+    let buckets = if end > start {
+        start / bucket_width..(end - 1) / bucket_width + 1
+    } else {
+        0..0
+    };
+    buckets.map(move |b| {
+        let bucket_start = b * bucket_width;
+        let overlap = end.min(bucket_start + bucket_width) - start.max(bucket_start);
+        (bucket_start, overlap as f64 / bucket_width as f64)
+    })
+}
+
+/// Resource usage averaged over a time bucket, in the trace's normalized units.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Utilization {
+    pub cpu: f64,
+    pub canonical_memory: f64,
+    pub assigned_memory: f64,
+    pub page_cache: f64,
+    pub disk_io_time: f64,
+}
+
+impl Utilization {
+    fn of(u: &TaskUsageRecord) -> Self {
+        Utilization {
+            cpu: u.cpu_rate.unwrap_or(0.0),
+            canonical_memory: u.canonical_mem_usage.unwrap_or(0.0),
+            assigned_memory: u.assigned_mem_usage.unwrap_or(0.0),
+            page_cache: u.total_page_cache.unwrap_or(0.0),
+            disk_io_time: u.disk_io_time.unwrap_or(0.0),
+        }
+    }
+
+    fn add_scaled(&mut self, other: &Utilization, factor: f64) {
+        self.cpu += other.cpu * factor;
+        self.canonical_memory += other.canonical_memory * factor;
+        self.assigned_memory += other.assigned_memory * factor;
+        self.page_cache += other.page_cache * factor;
+        self.disk_io_time += other.disk_io_time * factor;
+    }
+}
+
+/// Cluster-wide and per-machine resource usage, aggregated from task usage records into
+/// fixed-width time buckets.
+///
+/// Each bucket holds the time-weighted average of the summed usage of all tasks over the
+/// bucket, so a sample covering only part of a bucket contributes in proportion to its overlap.
+/// Values missing from a sample count as zero.
+#[derive(Clone, Debug)]
+pub struct UtilizationSeries {
+    bucket_width: u64,
+    cluster: BTreeMap<u64, Utilization>,
+    machines: HashMap<u64, BTreeMap<u64, Utilization>>,
+}
+
+impl Default for UtilizationSeries {
+    fn default() -> Self {
+        Self::with_bucket_width(MEASUREMENT_PERIOD)
+    }
+}
+
+impl UtilizationSeries {
+    /// Creates an empty series with buckets as long as the trace's measurement periods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty series with buckets of `bucket_width` microseconds.
+    pub fn with_bucket_width(bucket_width: u64) -> Self {
+        assert!(bucket_width > 0, "bucket width must be positive");
+        UtilizationSeries {
+            bucket_width,
+            cluster: BTreeMap::new(),
+            machines: HashMap::new(),
+        }
+    }
+
+    /// Aggregates all task usage records of the trace at `trace_path`, processing part files in
+    /// parallel.
+    pub fn from_trace(trace_path: &str, bucket_width: u64) -> Result<Self, Error> {
+        par_map_reduce_parts::<TaskUsageRecord, _, _, _, _>(
+            trace_path,
+            || Self::with_bucket_width(bucket_width),
+            |part| {
+                let mut series = Self::with_bucket_width(bucket_width);
+                for u in part {
+                    series.record(&u?);
+                }
+                Ok(series)
+            },
+            |mut a, b| {
+                a.merge(b);
+                a
+            },
+        )
+    }
+
+    pub fn bucket_width(&self) -> u64 {
+        self.bucket_width
+    }
+
+    /// Adds a usage sample to the buckets it overlaps.
+    pub fn record(&mut self, u: &TaskUsageRecord) {
+        let usage = Utilization::of(u);
+        let machine = self.machines.entry(u.machine_id).or_default();
+        for (bucket_start, weight) in bucket_overlaps(u.start_time, u.end_time, self.bucket_width) {
+            self.cluster
+                .entry(bucket_start)
+                .or_default()
+                .add_scaled(&usage, weight);
+            machine
+                .entry(bucket_start)
+                .or_default()
+                .add_scaled(&usage, weight);
+        }
+    }
+
+    /// Adds the samples aggregated in another series with the same bucket width (e.g., one
+    /// computed from a different part file).
+    pub fn merge(&mut self, other: UtilizationSeries) {
+        assert_eq!(
+            self.bucket_width, other.bucket_width,
+            "cannot merge series with different bucket widths"
+        );
+        for (b, u) in other.cluster {
+            self.cluster.entry(b).or_default().add_scaled(&u, 1.0);
+        }
+        for (m, buckets) in other.machines {
+            let machine = self.machines.entry(m).or_default();
+            for (b, u) in buckets {
+                machine.entry(b).or_default().add_scaled(&u, 1.0);
+            }
+        }
+    }
+
+    /// The cluster-wide usage, as `(bucket start time, usage)` pairs in time order. Buckets
+    /// without any samples are omitted.
+    pub fn cluster(&self) -> impl Iterator<Item = (u64, &Utilization)> {
+        self.cluster.iter().map(|(b, u)| (*b, u))
+    }
+
+    /// The IDs of the machines with usage samples.
+    pub fn machine_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.machines.keys().cloned()
+    }
+
+    /// A machine's usage, as `(bucket start time, usage)` pairs in time order.
+    pub fn machine(&self, machine_id: u64) -> impl Iterator<Item = (u64, &Utilization)> {
+        self.machines
+            .get(&machine_id)
+            .into_iter()
+            .flatten()
+            .map(|(b, u)| (*b, u))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{parse, scratch_dir, write_part};
+
+    fn row(start_time: u64, end_time: u64, machine_id: u64, cpu_rate: f64) -> String {
+        format!(
+            "{},{},1,0,{},{},0.5,,,,,,,,,,,,,",
+            start_time, end_time, machine_id, cpu_rate
+        )
+    }
+
+    /// One sample spanning two buckets of width 100 and two falling within one.
+    fn rows() -> Vec<String> {
+        vec![
+            row(50, 150, 1, 0.5),
+            row(100, 200, 1, 0.25),
+            row(150, 200, 2, 0.75),
+        ]
+    }
+
+    fn cpu<'a, I: Iterator<Item = (u64, &'a Utilization)>>(series: I) -> Vec<(u64, f64)> {
+        series.map(|(b, u)| (b, u.cpu)).collect()
+    }
+
+    #[test]
+    fn bucket_overlaps_are_fractions_of_buckets() {
+        let overlaps = |start, end| bucket_overlaps(start, end, 100).collect::<Vec<_>>();
+        assert_eq!(overlaps(50, 150), vec![(0, 0.5), (100, 0.5)]);
+        assert_eq!(overlaps(100, 200), vec![(100, 1.0)]);
+        assert_eq!(overlaps(0, 250), vec![(0, 1.0), (100, 1.0), (200, 0.5)]);
+        assert!(overlaps(100, 100).is_empty());
+    }
+
+    #[test]
+    fn samples_are_weighted_by_their_overlap() {
+        let mut series = UtilizationSeries::with_bucket_width(100);
+        for r in rows() {
+            series.record(&parse(&r));
+        }
+        assert_eq!(cpu(series.cluster()), vec![(0, 0.25), (100, 0.875)]);
+        assert_eq!(cpu(series.machine(1)), vec![(0, 0.25), (100, 0.5)]);
+        assert_eq!(cpu(series.machine(2)), vec![(100, 0.375)]);
+        assert!(cpu(series.machine(3)).is_empty());
+        let memory: Vec<f64> = series.cluster().map(|(_, u)| u.canonical_memory).collect();
+        assert_eq!(memory, vec![0.25, 1.0]);
+        assert_eq!(series.cluster().next().unwrap().1.page_cache, 0.0);
+    }
+
+    #[test]
+    fn merge_matches_recording_sequentially() {
+        let mut all = UtilizationSeries::with_bucket_width(100);
+        let mut halves = (
+            UtilizationSeries::with_bucket_width(100),
+            UtilizationSeries::with_bucket_width(100),
+        );
+        for (i, r) in rows().iter().enumerate() {
+            let u = parse(r);
+            all.record(&u);
+            if i % 2 == 0 {
+                halves.0.record(&u);
+            } else {
+                halves.1.record(&u);
+            }
+        }
+        let mut merged = halves.0;
+        merged.merge(halves.1);
+        assert_eq!(cpu(merged.cluster()), cpu(all.cluster()));
+        for m in 1..=2 {
+            assert_eq!(cpu(merged.machine(m)), cpu(all.machine(m)));
+        }
+
+        // the same, across the part files of a trace
+        let trace = scratch_dir();
+        let rows = rows();
+        let (first, second) = (&rows[..1], &rows[1..]);
+        for (part, rows) in [first, second].iter().enumerate() {
+            let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+            let name = format!("part-{:05}-of-00002.csv", part);
+            write_part(&trace, "task_usage", &name, &rows);
+        }
+        let from_trace = UtilizationSeries::from_trace(trace.to_str().unwrap(), 100).unwrap();
+        assert_eq!(cpu(from_trace.cluster()), cpu(all.cluster()));
+        let mut machines: Vec<u64> = from_trace.machine_ids().collect();
+        machines.sort_unstable();
+        assert_eq!(machines, vec![1, 2]);
+    }
+}